@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use condition_variable::ConditionVariable;
+
+const LEN: usize = 64 * 1024;
+
+/// reading a large value: `get` clones all `LEN` bytes, `get_arc` only bumps a refcount
+fn get(c: &mut Criterion) {
+	let cvar = ConditionVariable::new(vec![0u8; LEN]);
+	c.bench_function("get, 64KiB clone", |b| b.iter(|| cvar.get().unwrap()));
+
+	let cvar = ConditionVariable::new(Arc::new(vec![0u8; LEN]));
+	c.bench_function("get_arc, 64KiB", |b| b.iter(|| cvar.get_arc()));
+}
+
+criterion_group!(benches, get);
+criterion_main!(benches);
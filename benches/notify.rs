@@ -0,0 +1,59 @@
+use std::env;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use condition_variable::{ConditionVariable, Notify};
+
+const WAITERS: usize = 8;
+
+/// `set`s an unchanged value while `WAITERS` threads wait for a value that never comes;
+/// without the change-detection gate every `set` wakes all of them. besides the `set` latency,
+/// with `BENCH_WAKEUPS` set in the environment it reports to stderr how often the waiters'
+/// predicates were re-checked per `set`, i.e. the wakeups it caused
+fn bench_set_unchanged(c: &mut Criterion, name: &str, cvar: ConditionVariable<u32>) {
+	let cvar = Arc::new(cvar);
+	let checks = Arc::new(AtomicU64::new(0));
+
+	let waiters: Vec<_> = (0..WAITERS).map(|_| {
+		let cvar = cvar.clone();
+		let checks = checks.clone();
+		spawn(move || cvar.wait_for_condition(|v| {
+			checks.fetch_add(1, Ordering::Relaxed);
+			*v == u32::MAX
+		}).unwrap())
+	}).collect();
+	while cvar.waiter_count() < WAITERS {
+		sleep(Duration::from_millis(1));
+	}
+
+	let before = checks.load(Ordering::Relaxed);
+	let mut sets = 0u64;
+	c.bench_function(name, |b| b.iter(|| {
+		sets += 1;
+		cvar.set(0, Notify::All)
+	}));
+	// let the last woken waiters get to their predicate
+	sleep(Duration::from_millis(100));
+	let wakeups = checks.load(Ordering::Relaxed) - before;
+	if env::var_os("BENCH_WAKEUPS").is_some() {
+		eprintln!("{}: {:.2} wakeups per set", name, wakeups as f64 / sets as f64);
+	}
+
+	cvar.set(u32::MAX, Notify::All);
+	for waiter in waiters {
+		waiter.join().unwrap();
+	}
+}
+
+fn notify(c: &mut Criterion) {
+	bench_set_unchanged(c, "set unchanged, always notify", ConditionVariable::new(0));
+	bench_set_unchanged(c, "set unchanged, notify on change only",
+		ConditionVariable::builder().value(0).notify_on_change_only().build());
+}
+
+criterion_group!(benches, notify);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+use std::thread::spawn;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use condition_variable::{ConditionVariable, Notify};
+
+/// a wait whose condition already holds never touches the condvar, one that doesn't parks
+/// until another thread answers
+fn wait(c: &mut Criterion) {
+	let cvar = ConditionVariable::new(true);
+	c.bench_function("wait_for, already satisfied", |b| b.iter(|| cvar.wait_for(true).unwrap()));
+
+	let cvar = Arc::new(ConditionVariable::new(0u64));
+	let responder = {
+		let cvar = cvar.clone();
+		spawn(move || loop {
+			let request = cvar.wait_for_condition(|v| v % 2 == 1).unwrap();
+			if request == u64::MAX {
+				return;
+			}
+			cvar.set(request + 1, Notify::All);
+		})
+	};
+
+	let mut next = 1;
+	c.bench_function("wait_for, parking", |b| b.iter(|| {
+		cvar.set_and_wait_for(next, |v| *v == next + 1).unwrap();
+		next += 2;
+	}));
+
+	cvar.set(u64::MAX, Notify::All);
+	responder.join().unwrap();
+}
+
+criterion_group!(benches, wait);
+criterion_main!(benches);
@@ -0,0 +1,40 @@
+use alloc::sync::Arc;
+
+use crate::{ConditionVariable, Notify};
+
+/// storing `Arc<T>` makes reads O(1) however large `T` is: `get_arc` bumps a refcount where
+/// `get` would clone the whole value. writers swap in a new `Arc`, readers keep their snapshot
+impl<T> ConditionVariable<Arc<T>> {
+	/// a cheap handle to the current value, never a deep clone; reads through poisoning like `get_or_recover`
+	pub fn get_arc(&self) -> Arc<T> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock()).unwrap_or_else(|poison| poison.into_inner());
+		Arc::clone(&data)
+	}
+
+	/// wraps `value` in a fresh `Arc` and sets it; `false` while frozen, like `set`
+	pub fn set_value(&self, value: T, notify: Notify) -> bool {
+		self.set(Arc::new(value), notify)
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_get_arc() {
+		let cvar = ConditionVariable::new(Arc::new(vec![1, 2, 3]));
+
+		let before = cvar.get_arc();
+		assert!(Arc::ptr_eq(&before, &cvar.get_arc()));
+
+		cvar.set_value(vec![4], Notify::All);
+		assert_eq!(*before, vec![1, 2, 3]);
+		assert_eq!(*cvar.get_arc(), vec![4]);
+	}
+}
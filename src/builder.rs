@@ -0,0 +1,71 @@
+#[cfg(feature = "tracing")]
+use core::fmt;
+
+use crate::{ConditionVariable, Notify};
+#[cfg(feature = "tracing")]
+use crate::DebugFn;
+
+/// configures a `ConditionVariable` before constructing it, see `ConditionVariable::builder()`
+pub struct Builder<T> {
+	value: Option<T>,
+	default_notify: Notify,
+	#[cfg(feature = "tracing")]
+	debug_value: Option<DebugFn<T>>,
+	skip_unchanged: Option<fn(&T, &T) -> bool>,
+}
+
+impl<T> Builder<T> {
+	pub(crate) fn new() -> Builder<T> {
+		Builder {
+			value: None,
+			default_notify: Notify::All,
+			#[cfg(feature = "tracing")]
+			debug_value: None,
+			skip_unchanged: None,
+		}
+	}
+
+	/// the initial value (required)
+	pub fn value(mut self, value: T) -> Builder<T> {
+		self.value = Some(value);
+		self
+	}
+
+	/// the notify mode used by `set_default` (defaults to `Notify::All`)
+	pub fn default_notify(mut self, notify: Notify) -> Builder<T> {
+		self.default_notify = notify;
+		self
+	}
+
+	/// panics if no initial value was given
+	pub fn build(self) -> ConditionVariable<T> {
+		let value = self.value.expect("ConditionVariable::builder() requires a value");
+
+		let mut cvar = ConditionVariable::new(value);
+		cvar.default_notify = self.default_notify;
+		cvar.skip_unchanged = self.skip_unchanged;
+		#[cfg(feature = "tracing")]
+		{
+			cvar.debug_value = self.debug_value;
+		}
+		cvar
+	}
+}
+
+impl<T:PartialEq> Builder<T> {
+	/// makes `set` and `set_default` skip both the write and the notify if the value is unchanged,
+	/// so waiters aren't woken just to find their predicate still false
+	pub fn notify_on_change_only(mut self) -> Builder<T> {
+		self.skip_unchanged = Some(<T as PartialEq>::eq);
+		self
+	}
+}
+
+#[cfg(feature = "tracing")]
+impl<T:fmt::Debug> Builder<T> {
+	/// includes the new value in the `tracing` event emitted on every write
+	pub fn trace_values(mut self) -> Builder<T> {
+		self.debug_value = Some(<T as fmt::Debug>::fmt);
+		self
+	}
+}
@@ -0,0 +1,65 @@
+use crate::ConditionVariable;
+
+/// blocking iterator over value changes, see `ConditionVariable::changes()`
+///
+/// rapid changes may be coalesced: it yields the latest value, not every intermediate one.
+/// the iterator ends if the lock is poisoned or the condition variable is closed.
+pub struct Changes<'a, T> {
+	cvar: &'a ConditionVariable<T>,
+	last: Option<T>,
+}
+
+impl<'a, T:PartialEq+Clone> Iterator for Changes<'a, T> {
+	type Item = T;
+
+	fn next(&mut self) -> Option<T> {
+		let value = match self.last.take() {
+			None => self.cvar.get().ok()?,
+			Some(last) => self.cvar.wait_for_change(last).ok()?,
+		};
+
+		self.last = Some(value.clone());
+		Some(value)
+	}
+}
+
+impl<T:PartialEq+Clone> ConditionVariable<T> {
+	/// yields the current value immediately, then blocks for each change
+	pub fn changes(&self) -> Changes<'_, T> {
+		Changes {
+			cvar: self,
+			last: None,
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::{sleep, spawn};
+	use std::time::Duration;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_changes() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let consumer = spawn(move || {
+			cvar2.changes().take_while(|v| *v != 3).collect::<Vec<_>>()
+		});
+
+		for i in 1..4 {
+			while cvar1.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			cvar1.set(i, Notify::All);
+		}
+
+		let seen = consumer.join().unwrap();
+		assert_eq!(seen[0], 0);
+		assert!(seen.windows(2).all(|w| w[0] < w[1]));
+	}
+}
@@ -0,0 +1,19 @@
+use std::time::Instant;
+
+/// source of the current time for timed waits, see `ConditionVariable::with_clock`
+///
+/// waits still block on the condvar in real time; after moving a fake clock forward, call
+/// `ConditionVariable::notify` so waiters re-check their deadlines against it.
+pub trait Clock: Send + Sync {
+	fn now(&self) -> Instant;
+}
+
+/// the real monotonic clock, `Instant::now()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now(&self) -> Instant {
+		Instant::now()
+	}
+}
@@ -0,0 +1,115 @@
+use alloc::collections::VecDeque;
+
+use crate::Notify;
+
+/// FIFO bookkeeping for `ConditionVariable::new_fair`: untimed waiters queue up with a ticket, and
+/// a `Notify::One` lets only the oldest one through while the others go back to sleep
+pub(crate) struct Fairness {
+	/// tickets of the waiters currently blocked, oldest first
+	queue: VecDeque<u64>,
+	next_ticket: u64,
+	/// wakeups from `Notify::One`/`Many` not yet taken; never more than there are waiters
+	grants: usize,
+	/// bumped by `Notify::All`, which releases every waiter
+	broadcasts: u64,
+}
+
+impl Fairness {
+	pub(crate) fn new() -> Fairness {
+		Fairness {
+			queue: VecDeque::new(),
+			next_ticket: 0,
+			grants: 0,
+			broadcasts: 0,
+		}
+	}
+
+	/// queues a waiter; returns its ticket and the broadcast count to compare against later
+	pub(crate) fn enqueue(&mut self) -> (u64, u64) {
+		let ticket = self.next_ticket;
+		self.next_ticket = self.next_ticket.wrapping_add(1);
+		self.queue.push_back(ticket);
+
+		(ticket, self.broadcasts)
+	}
+
+	/// whether the waiter holding `ticket` may return from its wait, in which case it is dequeued
+	pub(crate) fn try_release(&mut self, ticket: u64, broadcasts: u64) -> bool {
+		if self.broadcasts != broadcasts {
+			self.leave(ticket);
+			return true;
+		}
+		if self.grants > 0 && self.queue.front() == Some(&ticket) {
+			self.grants -= 1;
+			self.queue.pop_front();
+			return true;
+		}
+		false
+	}
+
+	/// dequeues a waiter that gives up, e.g. on cancellation
+	pub(crate) fn leave(&mut self, ticket: u64) {
+		self.queue.retain(|t| *t != ticket);
+		self.grants = self.grants.min(self.queue.len());
+	}
+
+	pub(crate) fn notify(&mut self, notify: Notify) {
+		match notify {
+			Notify::None => {},
+			Notify::One => self.grants = (self.grants + 1).min(self.queue.len()),
+			Notify::Many(n) => self.grants = self.grants.saturating_add(n).min(self.queue.len()),
+			Notify::All => {
+				self.broadcasts = self.broadcasts.wrapping_add(1);
+				self.grants = 0;
+			},
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::{Arc, Mutex};
+	use std::thread::{sleep, spawn};
+	use std::time::Duration;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_fair_fifo() {
+		let cvar = Arc::new(ConditionVariable::new_fair(0));
+		let order = Arc::new(Mutex::new(Vec::new()));
+
+		let waiters: Vec<_> = (0..4).map(|i| {
+			let (cvar2, order2) = (cvar.clone(), order.clone());
+			let waiter = spawn(move || {
+				cvar2.wait_for_condition_mut(|tokens| {
+					if *tokens == 0 {
+						return false;
+					}
+					*tokens -= 1;
+					order2.lock().unwrap().push(i);
+					true
+				}).unwrap();
+			});
+			while cvar.waiter_count() <= i {
+				sleep(Duration::from_millis(1));
+			}
+			// the waiter is counted just before it blocks, give it time to get there
+			sleep(Duration::from_millis(10));
+			waiter
+		}).collect();
+
+		for i in 0..4 {
+			cvar.update(|tokens| *tokens += 1, Notify::One);
+			while order.lock().unwrap().len() <= i {
+				sleep(Duration::from_millis(1));
+			}
+		}
+		for waiter in waiters {
+			waiter.join().unwrap();
+		}
+
+		assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3]);
+	}
+}
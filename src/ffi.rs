@@ -0,0 +1,85 @@
+//! a minimal C interface to a `ConditionVariable<bool>`
+//!
+//! `cv_bool_new` hands out an owning pointer that must eventually be passed to `cv_bool_free`
+//! exactly once. all other functions take it by shared reference, so any number of threads may
+//! call them concurrently, as long as none of them runs during or after `cv_bool_free`.
+
+use std::os::raw::c_int;
+
+use crate::{ConditionVariable, Notify, WaitResult};
+
+/// opaque to C
+pub struct CvBool(ConditionVariable<bool>);
+
+#[no_mangle]
+pub extern "C" fn cv_bool_new(value: bool) -> *mut CvBool {
+	Box::into_raw(Box::new(CvBool(ConditionVariable::new(value))))
+}
+
+/// # Safety
+/// `cv` must come from `cv_bool_new` and must not be used afterwards; null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn cv_bool_free(cv: *mut CvBool) {
+	if !cv.is_null() {
+		drop(Box::from_raw(cv));
+	}
+}
+
+/// sets the flag and wakes all waiters
+///
+/// # Safety
+/// `cv` must be a live pointer from `cv_bool_new`
+#[no_mangle]
+pub unsafe extern "C" fn cv_bool_set(cv: *const CvBool, value: bool) {
+	(*cv).0.set(value, Notify::All);
+}
+
+/// blocks until the flag equals `expected`; returns 0, or -1 if the lock is poisoned
+///
+/// # Safety
+/// `cv` must be a live pointer from `cv_bool_new`
+#[no_mangle]
+pub unsafe extern "C" fn cv_bool_wait(cv: *const CvBool, expected: bool) -> c_int {
+	match (*cv).0.wait_for(expected) {
+		Ok(_) => 0,
+		Err(_) => -1,
+	}
+}
+
+/// like `cv_bool_wait` with a timeout; returns 1 if the flag equals `expected`, 0 on timeout
+/// and -1 if the lock is poisoned
+///
+/// # Safety
+/// `cv` must be a live pointer from `cv_bool_new`
+#[no_mangle]
+pub unsafe extern "C" fn cv_bool_wait_ms(cv: *const CvBool, expected: bool, timeout_ms: i64) -> c_int {
+	match (*cv).0.wait_for_ms(expected, timeout_ms) {
+		Ok(res) if res.result == WaitResult::Satisfied => 1,
+		Ok(_) => 0,
+		Err(_) => -1,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread::spawn;
+
+	use crate::ffi::{cv_bool_free, cv_bool_new, cv_bool_set, cv_bool_wait, cv_bool_wait_ms};
+
+	#[test]
+	fn test_cv_bool() {
+		let cv = cv_bool_new(false);
+		let addr = cv as usize;
+
+		unsafe {
+			assert_eq!(cv_bool_wait_ms(cv, true, 10), 0);
+
+			let setter = spawn(move || cv_bool_set(addr as *const _, true));
+			assert_eq!(cv_bool_wait(cv, true), 0);
+			setter.join().unwrap();
+
+			assert_eq!(cv_bool_wait_ms(cv, true, 10), 1);
+			cv_bool_free(cv);
+		}
+	}
+}
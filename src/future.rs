@@ -0,0 +1,97 @@
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::{ConditionVariable, WaitError};
+
+/// future returned by `ConditionVariable::wait_for_condition_async`
+pub struct WaitForCondition<'a, T, F> {
+	cvar: &'a ConditionVariable<T>,
+	cond_func: F,
+}
+
+impl<'a, T, F> Unpin for WaitForCondition<'a, T, F> {}
+
+impl<'a, T:Clone, F:FnMut(&T) -> bool> Future for WaitForCondition<'a, T, F> {
+	type Output = Result<T, WaitError>;
+
+	fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let this = self.get_mut();
+		let cvar = this.cvar;
+		let (lock, _) = &cvar.pair;
+
+		let actual = match cvar.recover(lock.lock()) {
+			Ok(actual) => actual,
+			Err(_) => return Poll::Ready(Err(WaitError::Poisoned)),
+		};
+
+		if (this.cond_func)(&*actual) {
+			return Poll::Ready(Ok(actual.clone()));
+		}
+		if cvar.is_closed() {
+			return Poll::Ready(Err(WaitError::Closed));
+		}
+
+		cvar.register_waker(cx.waker());
+		drop(actual);
+
+		Poll::Pending
+	}
+}
+
+impl<T:Clone> ConditionVariable<T> {
+	/// like `wait_for_condition`, but yields to the executor instead of blocking the thread
+	pub fn wait_for_condition_async<F:FnMut(&T) -> bool>(&self, cond_func: F) -> WaitForCondition<'_, T, F> {
+		WaitForCondition {
+			cvar: self,
+			cond_func,
+		}
+	}
+}
+
+impl<T:PartialEq+Clone> ConditionVariable<T> {
+	pub async fn wait_for_async(&self, expected: T) -> Result<T, WaitError> {
+		self.wait_for_condition_async(|actual| *actual == expected).await
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[tokio::test]
+	async fn test_wait_for_async() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		let waiter = tokio::spawn(async move {
+			cvar1.wait_for_async(true).await.unwrap()
+		});
+		tokio::spawn(async move {
+			cvar2.set(true, Notify::All);
+		});
+
+		assert!(waiter.await.unwrap());
+	}
+
+	#[tokio::test]
+	async fn test_wait_for_async_mixed_with_sync() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let sync_waiter = spawn(move || cvar2.wait_for(2).unwrap());
+
+		let cvar3 = cvar1.clone();
+		tokio::spawn(async move {
+			cvar3.set(1, Notify::All);
+		});
+		assert_eq!(cvar1.wait_for_async(1).await.unwrap(), 1);
+
+		cvar1.set(2, Notify::All);
+		assert_eq!(sync_waiter.join().unwrap(), 2);
+	}
+}
@@ -0,0 +1,68 @@
+use crate::{ConditionVariable, Notify, WaitError};
+
+/// one-shot and latching, unlike `std::sync::Barrier`: closed until `open`, then open for good,
+/// e.g. to hold threads back until initialization is complete
+pub struct Gate {
+	open: ConditionVariable<bool>,
+}
+
+impl Gate {
+	pub fn new() -> Gate {
+		Gate {
+			open: ConditionVariable::new(false),
+		}
+	}
+
+	/// releases all current and future waiters
+	pub fn open(&self) {
+		self.open.set(true, Notify::All);
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.open.get_or_recover()
+	}
+
+	/// returns right away once the gate is open, blocks until then otherwise
+	pub fn wait(&self) -> Result<(), WaitError> {
+		self.open.wait_for(true).map(|_| ())
+	}
+
+	/// like `wait`, giving up after `timeout_ms`; returns whether the gate is open
+	#[cfg(feature = "std")]
+	pub fn wait_ms(&self, timeout_ms: i64) -> Result<bool, WaitError> {
+		self.open.wait_for_ms(true, timeout_ms).map(|res| res.result.is_satisfied())
+	}
+}
+
+impl Default for Gate {
+	fn default() -> Gate {
+		Gate::new()
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::Gate;
+
+	#[test]
+	fn test_gate() {
+		let gate = Arc::new(Gate::new());
+		assert!(!gate.is_open());
+		assert_eq!(gate.wait_ms(10), Ok(false));
+
+		let waiters: Vec<_> = (0..4).map(|_| {
+			let gate = gate.clone();
+			spawn(move || gate.wait())
+		}).collect();
+
+		gate.open();
+		for waiter in waiters {
+			assert_eq!(waiter.join().unwrap(), Ok(()));
+		}
+		assert!(gate.is_open());
+		assert_eq!(gate.wait_ms(0), Ok(true));
+	}
+}
@@ -0,0 +1,88 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use crate::sync::Mutex;
+use crate::ConditionVariable;
+
+/// the last few values with their generation, oldest first; see `ConditionVariable::new_with_history`
+pub(crate) struct History<T> {
+	len: usize,
+	/// `T::clone`, captured where `T: Clone` is known
+	clone_fn: fn(&T) -> T,
+	entries: Mutex<VecDeque<(u64, T)>>,
+}
+
+impl<T> History<T> {
+	/// called under the data lock, so entries are in generation order
+	pub(crate) fn record(&self, generation: u64, value: &T) {
+		let mut entries = self.entries.lock().unwrap_or_else(|poison| poison.into_inner());
+		if entries.len() == self.len {
+			entries.pop_front();
+		}
+		entries.push_back((generation, (self.clone_fn)(value)));
+	}
+}
+
+impl<T:Clone> ConditionVariable<T> {
+	/// keeps the last `history_len` written values around for `changes_since`
+	pub fn new_with_history(value: T, history_len: usize) -> ConditionVariable<T> {
+		let mut cvar = ConditionVariable::new(value);
+		if history_len > 0 {
+			cvar.history = Some(History {
+				len: history_len,
+				clone_fn: T::clone,
+				entries: Mutex::new(VecDeque::with_capacity(history_len)),
+			});
+		}
+		cvar
+	}
+
+	/// non-blocking catch-up: the current generation and the recorded values written after `seen`,
+	/// oldest first. values older than the history are lost, so fewer may come back than were written.
+	/// without history (`new`, or a length of 0) it returns just the current value, if it is newer than `seen`
+	pub fn changes_since(&self, seen: u64) -> (u64, Vec<T>) {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock()).unwrap_or_else(|poison| poison.into_inner());
+		let generation = self.generation();
+
+		let values = match &self.history {
+			Some(history) => {
+				let entries = history.entries.lock().unwrap_or_else(|poison| poison.into_inner());
+				entries.iter()
+					.filter(|(written, _)| crate::newer_generation(*written, seen))
+					.map(|(_, value)| value.clone())
+					.collect()
+			},
+			None if crate::newer_generation(generation, seen) => alloc::vec![data.clone()],
+			None => Vec::new(),
+		};
+
+		(generation, values)
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_changes_since() {
+		let cvar = ConditionVariable::new_with_history(0, 3);
+		let seen = cvar.generation();
+
+		for i in 1..6 {
+			cvar.set(i, Notify::All);
+		}
+
+		assert_eq!(cvar.changes_since(seen), (5, vec![3, 4, 5]));
+		assert_eq!(cvar.changes_since(3), (5, vec![4, 5]));
+		assert_eq!(cvar.changes_since(5), (5, vec![]));
+
+		let cvar = ConditionVariable::new(0);
+		cvar.set(1, Notify::All);
+		cvar.set(2, Notify::All);
+		assert_eq!(cvar.changes_since(0), (2, vec![2]));
+	}
+}
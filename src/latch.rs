@@ -0,0 +1,73 @@
+use crate::{ConditionVariable, WaitError};
+
+/// counts down from `count`; waiters are released, for good, once it reaches zero
+pub struct CountdownLatch {
+	count: ConditionVariable<usize>,
+}
+
+impl CountdownLatch {
+	pub fn new(count: usize) -> CountdownLatch {
+		CountdownLatch {
+			count: ConditionVariable::new(count),
+		}
+	}
+
+	/// decrements the count, waking all waiters when it hits zero; further calls do nothing
+	pub fn count_down(&self) {
+		self.count.modify(|count| {
+			if *count == 0 {
+				return (false, ());
+			}
+			*count -= 1;
+			(*count == 0, ())
+		}).unwrap();
+	}
+
+	/// the remaining count, a snapshot
+	pub fn count(&self) -> usize {
+		self.count.get_or_recover()
+	}
+
+	/// blocks until the count reaches zero
+	pub fn wait(&self) -> Result<(), WaitError> {
+		self.count.wait_for(0).map(|_| ())
+	}
+
+	/// like `wait`, giving up after `timeout_ms`; returns whether the count reached zero
+	#[cfg(feature = "std")]
+	pub fn wait_ms(&self, timeout_ms: i64) -> Result<bool, WaitError> {
+		self.count.wait_for_ms(0, timeout_ms).map(|res| res.result.is_satisfied())
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::CountdownLatch;
+
+	#[test]
+	fn test_countdown_latch() {
+		let latch = Arc::new(CountdownLatch::new(3));
+		assert_eq!(latch.wait_ms(10), Ok(false));
+
+		let waiter = {
+			let latch = latch.clone();
+			spawn(move || latch.wait())
+		};
+
+		let workers: Vec<_> = (0..3).map(|_| {
+			let latch = latch.clone();
+			spawn(move || latch.count_down())
+		}).collect();
+		for worker in workers {
+			worker.join().unwrap();
+		}
+
+		assert_eq!(waiter.join().unwrap(), Ok(()));
+		assert_eq!(latch.count(), 0);
+		latch.count_down();
+		assert_eq!(latch.wait_ms(10), Ok(true));
+	}
+}
@@ -1,148 +1,3225 @@
-use std::cmp::PartialEq;
-use std::sync::{Mutex, Condvar, PoisonError, MutexGuard, LockResult};
-use std::time::Instant;
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::PartialEq;
+use core::fmt;
+use core::hint::spin_loop;
+use core::mem;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use core::task::Poll;
+use core::time::Duration;
+#[cfg(feature = "async")]
+use core::task::Waker;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+#[cfg(feature = "std")]
+use std::error::Error;
+#[cfg(feature = "std")]
+use std::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+
+use crate::sync::{Mutex, Condvar};
+use crate::fair::Fairness;
+use crate::history::History;
+
+mod sync;
+#[cfg(feature = "std")]
+mod clock;
+#[cfg(feature = "std")]
+mod timed;
+#[cfg(feature = "std")]
+mod rw;
+mod arc_value;
+mod builder;
+mod changes;
+mod fair;
+mod gate;
+mod history;
+mod latch;
+mod notify_guard;
+mod shared;
+mod split;
+#[cfg(feature = "async")]
+mod future;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(feature = "crossbeam")]
+mod signal;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+pub use crate::builder::Builder;
+pub use crate::changes::Changes;
+pub use crate::gate::Gate;
+pub use crate::latch::CountdownLatch;
+pub use crate::notify_guard::NotifyGuard;
+pub use crate::shared::SharedConditionVariable;
+pub use crate::split::{Setter, Waiter};
+#[cfg(feature = "std")]
+pub use crate::clock::{Clock, SystemClock};
+#[cfg(feature = "std")]
+pub use crate::timed::{Budget, Either, WaitStats, wait_for_either};
+#[cfg(feature = "std")]
+pub use crate::rw::RwConditionVariable;
+
+/// the lock types of whichever backend is enabled, as they appear in the API
+pub use crate::sync::{MutexGuard, PoisonError, LockResult, TryLockError};
+
+#[cfg(feature = "async")]
+pub use crate::future::WaitForCondition;
+#[cfg(feature = "async")]
+pub use crate::stream::ChangeStream;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Notify {
+	/// changes the value without waking anyone
+	None,
+	/// wakes one waiter, which needn't be one whose predicate the change satisfies; if the waiters
+	/// wait for different things, use `All` or `set_one_safe`, or the change can go unnoticed
 	One,
 	All,
+	/// wakes up to N waiters; `Condvar` gives no ordering guarantee, so these are *some* N waiters
+	Many(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitError {
+	/// a thread panicked while holding the lock
+	Poisoned,
+	/// `close()` was called, so the condition may never become true
+	Closed,
+	/// the flag attached via `attach_cancel` was set
+	Cancelled,
+	/// `wait_for_condition_detect_livelock` kept being woken without the value changing
+	SuspectedLivelock,
+	/// `freeze()` was called, so the write that would have preceded the wait was rejected
+	Frozen,
+}
+
+impl fmt::Display for WaitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			WaitError::Poisoned => write!(f, "lock poisoned by a panicking thread"),
+			WaitError::Closed => write!(f, "condition variable closed"),
+			WaitError::Cancelled => write!(f, "wait cancelled"),
+			WaitError::SuspectedLivelock => write!(f, "woken repeatedly without the value changing"),
+			WaitError::Frozen => write!(f, "condition variable frozen"),
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Error for WaitError {}
+
+impl<G> From<PoisonError<G>> for WaitError {
+	fn from(_: PoisonError<G>) -> WaitError {
+		WaitError::Poisoned
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitResult {
+	Satisfied,
+	TimedOut,
+}
+
+impl WaitResult {
+	pub fn is_satisfied(self) -> bool {
+		self == WaitResult::Satisfied
+	}
+}
+
+/// like `WaitResult`, carrying the value that satisfied the wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitOutcome<T> {
+	Ready(T),
+	TimedOut,
+	/// only from `wait_for_condition_full`, when its cancel flag was set
+	Cancelled,
+}
+
+/// outcome of a timed wait
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedWait {
+	pub result: WaitResult,
+	/// time left until the timeout, zero if it expired
+	pub remaining: Duration,
+	/// time spent in the wait, satisfied or not
+	pub elapsed: Duration,
+}
+
+/// snapshot of the usage counters, see `ConditionVariable::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CvStats {
+	/// `Notify::One` notifies, plus the wakeups a `Notify::Many` issued: at most one per waiter
+	/// blocked at the time. `set_one_safe` passing its notify on doesn't count again
+	pub notify_one_count: u64,
+	pub notify_all_count: u64,
+	/// calls to waiting methods, however often each one slept
+	pub wait_count: u64,
+	/// timed waits that gave up
+	pub timeout_count: u64,
+}
+
+/// returns whether to stay subscribed
+type Subscriber<T> = Box<dyn Fn(&T) -> bool + Send>;
+
+/// `<T as Debug>::fmt`, captured by `Builder::trace_values` where `T: Debug` is known
+#[cfg(feature = "tracing")]
+type DebugFn<T> = fn(&T, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+#[cfg(feature = "tracing")]
+struct DebugWith<'a, T>(&'a T, DebugFn<T>);
+
+#[cfg(feature = "tracing")]
+impl<'a, T> fmt::Debug for DebugWith<'a, T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		(self.1)(self.0, f)
+	}
+}
+
+/// source of `ConditionVariable::id`
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub struct ConditionVariable<T> {
+	pair: (Mutex<T>, Condvar),
+	/// 0 until `id` first assigns one, so `new` can stay `const`
+	id: AtomicUsize,
+	waiters: AtomicUsize,
+	/// bumped on every write, under the data lock
+	generation: AtomicU64,
+	/// bumped by every notify that wakes someone, so waiters can tell it from a spurious wakeup
+	notifications: AtomicU64,
+	closed: AtomicBool,
+	/// set by `freeze`, read under the data lock
+	frozen: AtomicBool,
+	/// how many more times a `set_one_safe` notify may be passed on, see `relay`;
+	/// only touched under the data lock, and cleared by the next write
+	relay: AtomicUsize,
+	notify_one_count: AtomicU64,
+	notify_all_count: AtomicU64,
+	wait_count: AtomicU64,
+	timeout_count: AtomicU64,
+	ignore_poison: bool,
+	#[cfg(feature = "tracing")]
+	debug_value: Option<DebugFn<T>>,
+	/// `T::eq`, if configured via `Builder::notify_on_change_only`
+	skip_unchanged: Option<fn(&T, &T) -> bool>,
+	/// recent values, if enabled via `new_with_history`
+	history: Option<History<T>>,
+	/// FIFO wakeups, if constructed via `new_fair`
+	fair: Option<Mutex<Fairness>>,
+	/// kept for `reset`, if constructed via `new_resettable`; behind a lock so `T` needn't be `Sync`
+	initial: Option<Mutex<T>>,
+	/// `None` for `SystemClock`, which keeps `new` a `const fn`
+	#[cfg(feature = "std")]
+	clock: Option<Box<dyn Clock>>,
+	/// set via `attach_cancel`
+	#[cfg(feature = "std")]
+	cancel: Mutex<Option<Arc<AtomicBool>>>,
+	default_notify: Notify,
+	subscribers: Mutex<Vec<Subscriber<T>>>,
+	#[cfg(feature = "async")]
+	wakers: Mutex<Vec<Waker>>,
+}
+
+/// whether `generation` is later than `seen`, also if the counter wrapped around in between
+fn newer_generation(generation: u64, seen: u64) -> bool {
+	(generation.wrapping_sub(seen) as i64) > 0
+}
+
+/// counts a thread as waiting for as long as it is alive
+struct WaiterGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaiterGuard<'a> {
+	/// also counts the wait for `stats`
+	fn new<T>(cvar: &'a ConditionVariable<T>) -> WaiterGuard<'a> {
+		cvar.count_wait();
+		WaiterGuard::register(cvar)
+	}
+
+	/// for waits that were already counted
+	fn register<T>(cvar: &'a ConditionVariable<T>) -> WaiterGuard<'a> {
+		cvar.waiters.fetch_add(1, Ordering::SeqCst);
+		WaiterGuard(&cvar.waiters)
+	}
+}
+
+impl<'a> Drop for WaiterGuard<'a> {
+	fn drop(&mut self) {
+		self.0.fetch_sub(1, Ordering::SeqCst);
+	}
 }
 
-pub struct ConditionVariable<T> {
-	pair: (Mutex<T>, Condvar)
-}
+impl<T> ConditionVariable<T> {
+	/// `const`, so a condition variable can be a `static` without lazy initialization
+	pub const fn new(value: T) -> ConditionVariable<T> {
+		ConditionVariable {
+			pair: (Mutex::new(value), Condvar::new()),
+			id: AtomicUsize::new(0),
+			waiters: AtomicUsize::new(0),
+			generation: AtomicU64::new(0),
+			notifications: AtomicU64::new(0),
+			closed: AtomicBool::new(false),
+			frozen: AtomicBool::new(false),
+			relay: AtomicUsize::new(0),
+			notify_one_count: AtomicU64::new(0),
+			notify_all_count: AtomicU64::new(0),
+			wait_count: AtomicU64::new(0),
+			timeout_count: AtomicU64::new(0),
+			ignore_poison: false,
+			#[cfg(feature = "tracing")]
+			debug_value: None,
+			skip_unchanged: None,
+			history: None,
+			fair: None,
+			initial: None,
+			#[cfg(feature = "std")]
+			clock: None,
+			#[cfg(feature = "std")]
+			cancel: Mutex::new(None),
+			default_notify: Notify::All,
+			subscribers: Mutex::new(Vec::new()),
+			#[cfg(feature = "async")]
+			wakers: Mutex::new(Vec::new()),
+		}
+	}
+
+	/// builds the initial value with `f`, e.g. a buffer with preallocated capacity
+	pub fn from_fn<F:FnOnce() -> T>(f: F) -> ConditionVariable<T> {
+		ConditionVariable::new(f())
+	}
+
+	/// never reports poisoning: every lock taken internally recovers the guard, so `Result`s are
+	/// always `Ok`. only use this if a panic can't leave the value half-updated, since waiters and
+	/// readers will see whatever state the panicking thread left behind
+	pub fn new_ignoring_poison(value: T) -> ConditionVariable<T> {
+		ConditionVariable {
+			ignore_poison: true,
+			..ConditionVariable::new(value)
+		}
+	}
+
+	/// approximately FIFO wakeups to keep waiters from starving: `Notify::One` wakes everyone, but
+	/// only the longest-blocked waiter proceeds, the rest go back to sleep. costs a thundering herd per
+	/// notify; timed waits don't queue up and treat every notify as their own
+	pub fn new_fair(value: T) -> ConditionVariable<T> {
+		ConditionVariable {
+			fair: Some(Mutex::new(Fairness::new())),
+			..ConditionVariable::new(value)
+		}
+	}
+
+	/// reads the time for timed waits from `clock` instead of `Instant::now()`, e.g. a fake clock in tests
+	#[cfg(feature = "std")]
+	pub fn with_clock<C:Clock + 'static>(value: T, clock: C) -> ConditionVariable<T> {
+		ConditionVariable {
+			clock: Some(Box::new(clock)),
+			..ConditionVariable::new(value)
+		}
+	}
+
+	pub fn builder() -> Builder<T> {
+		Builder::new()
+	}
+
+	/// consumes the condition variable and returns the value
+	pub fn into_inner(self) -> Result<T, PoisonError<T>> {
+		let ignore_poison = self.ignore_poison;
+		let (lock, _) = self.pair;
+
+		match lock.into_inner() {
+			Err(poison) if ignore_poison => Ok(poison.into_inner()),
+			res => res,
+		}
+	}
+
+	/// converts the value into a fresh `ConditionVariable<B>`, keeping only the default notify mode;
+	/// a setup-time transform, since `self` is consumed there can't be waiters, and subscribers are dropped.
+	/// a poisoned value is converted as is
+	pub fn map_into<B, F:FnOnce(T) -> B>(self, f: F) -> ConditionVariable<B> {
+		let default_notify = self.default_notify;
+		let value = self.into_inner().unwrap_or_else(PoisonError::into_inner);
+
+		let mut cvar = ConditionVariable::new(f(value));
+		cvar.default_notify = default_notify;
+		cvar
+	}
+
+	/// gives mutable access without locking, since `&mut self` guarantees exclusivity
+	pub fn get_mut(&mut self) -> Result<&mut T, PoisonError<&mut T>> {
+		let ignore_poison = self.ignore_poison;
+		let (lock, _) = &mut self.pair;
+
+		match lock.get_mut() {
+			Err(poison) if ignore_poison => Ok(poison.into_inner()),
+			res => res,
+		}
+	}
+
+	/// whether a thread panicked while holding the lock; doesn't take the lock.
+	/// reports the truth even with `new_ignoring_poison`
+	pub fn is_poisoned(&self) -> bool {
+		let (lock, _) = &self.pair;
+
+		lock.is_poisoned()
+	}
+
+	/// marks the lock healthy again after a panic, so calls return `Ok` instead of the poison error;
+	/// only call it once the value has been checked, or repaired via `lock()`
+	pub fn clear_poison(&self) {
+		let (lock, _) = &self.pair;
+
+		lock.clear_poison();
+	}
+
+	/// unique per instance and fixed for its lifetime, e.g. to key a `HashMap` of condition variables;
+	/// two ids are equal only for the same instance, whatever the values
+	pub fn id(&self) -> usize {
+		let id = self.id.load(Ordering::SeqCst);
+		if id != 0 {
+			return id;
+		}
+
+		let fresh = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+		match self.id.compare_exchange(0, fresh, Ordering::SeqCst, Ordering::SeqCst) {
+			Ok(_) => fresh,
+			Err(id) => id,
+		}
+	}
+
+	/// number of threads currently blocked in a wait; a best-effort snapshot, not a synchronization point
+	pub fn waiter_count(&self) -> usize {
+		self.waiters.load(Ordering::SeqCst)
+	}
+
+	/// how often this condition variable notified, waited and timed out so far; each counter is exact,
+	/// but they are read one after the other, so the snapshot needn't be consistent across them
+	pub fn stats(&self) -> CvStats {
+		CvStats {
+			notify_one_count: self.notify_one_count.load(Ordering::Relaxed),
+			notify_all_count: self.notify_all_count.load(Ordering::Relaxed),
+			wait_count: self.wait_count.load(Ordering::Relaxed),
+			timeout_count: self.timeout_count.load(Ordering::Relaxed),
+		}
+	}
+
+	/// counts a call to a waiting method, for `stats`
+	fn count_wait(&self) {
+		self.wait_count.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// counts a timed wait that gave up, for `stats`
+	fn timed_out(&self) {
+		self.timeout_count.fetch_add(1, Ordering::Relaxed);
+	}
+
+	/// number of writes so far; unlike the value itself, it never returns to an earlier state.
+	/// every `lock()` counts as a write, since the guard may have been written through
+	pub fn generation(&self) -> u64 {
+		self.generation.load(Ordering::SeqCst)
+	}
+
+	/// returns `false`, leaving the value alone, while frozen (see `freeze`)
+	pub fn set(&self, value: T, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		self.store(cvar, &mut data, value, notify)
+	}
+
+	/// `set` once the lock is held, shared with the other ways of taking it
+	fn store(&self, cvar: &Condvar, data: &mut T, value: T, notify: Notify) -> bool {
+		if self.is_frozen() {
+			return false;
+		}
+		if let Some(eq) = self.skip_unchanged {
+			if eq(data, &value) {
+				return true;
+			}
+		}
+		*data = value;
+
+		self.changed(cvar, data, notify);
+		true
+	}
+
+	/// assigns each value in turn under a single lock and notifies once;
+	/// waiters only ever observe the last value, never the intermediate ones; `false` while frozen
+	pub fn batch_set<I:IntoIterator<Item=T>>(&self, values: I, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return false;
+		}
+		for value in values {
+			*data = value;
+		}
+
+		self.changed(cvar, &*data, notify);
+		true
+	}
+
+	/// like `set`, using the notify mode configured via `Builder::default_notify`
+	pub fn set_default(&self, value: T) -> bool {
+		self.set(value, self.default_notify)
+	}
+
+	/// sets `value` and returns the previous one, see `replace`
+	pub fn set_and_get(&self, value: T, notify: Notify) -> Result<T, T> {
+		self.replace(value, notify)
+	}
+
+	/// swaps in `value` and returns the previous value by move; neither is cloned.
+	/// while frozen it hands `value` back as `Err`
+	pub fn replace(&self, value: T, notify: Notify) -> Result<T, T> {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return Err(value);
+		}
+		let previous = mem::replace(&mut *data, value);
+
+		self.changed(cvar, &*data, notify);
+		Ok(previous)
+	}
+
+	/// resets the value to `T::default()` and returns the previous one, e.g. to drain a batch;
+	/// `None` while frozen
+	pub fn take(&self, notify: Notify) -> Option<T>
+		where T: Default
+	{
+		self.replace(T::default(), notify).ok()
+	}
+
+	/// mutates the value in place and notifies afterwards; like `set`, it returns `false`
+	/// without calling `f` while frozen
+	pub fn update<F:FnOnce(&mut T)>(&self, f: F, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return false;
+		}
+		f(&mut *data);
+
+		self.changed(cvar, &*data, notify);
+		true
+	}
+
+	/// mutates the value; `f` returns whether to notify all waiters, along with a result for the caller.
+	/// `None`, without calling `f`, while frozen
+	pub fn modify<R, F:FnOnce(&mut T) -> (bool, R)>(&self, f: F) -> Option<R> {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return None;
+		}
+		let (should_notify, res) = f(&mut *data);
+
+		if should_notify {
+			self.changed(cvar, &*data, Notify::All);
+		}
+		Some(res)
+	}
+
+	/// like `set` with `Notify::One`, but a woken waiter whose predicate still fails passes the notify
+	/// on before it goes back to sleep, up to once per waiter blocked at the time of the write. with
+	/// the OS's usual first-in-first-out wakeups (guaranteed with `new_fair`) that reaches every waiter.
+	/// `false` while frozen
+	pub fn set_one_safe(&self, value: T) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return false;
+		}
+		*data = value;
+
+		self.changed(cvar, &*data, Notify::One);
+		self.relay.store(self.waiter_count(), Ordering::SeqCst);
+		true
+	}
+
+	/// sets `value` only if `pred` holds for the current value; returns whether it did
+	pub fn set_if<P:Fn(&T) -> bool>(&self, value: T, pred: P, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() || !pred(&*data) {
+			return false;
+		}
+		*data = value;
+
+		self.changed(cvar, &*data, notify);
+		true
+	}
+
+	/// backpressure for producers: writes and notifies only if at least `min_waiters` threads
+	/// (so pass 1 or more) are blocked in a wait, otherwise leaves the value alone; returns whether it wrote.
+	/// the count is read under the lock, but a waiter that was just woken counts until it returns
+	pub fn try_set(&self, value: T, min_waiters: usize, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() || self.waiter_count() < min_waiters {
+			return false;
+		}
+		*data = value;
+
+		self.changed(cvar, &*data, notify);
+		true
+	}
+
+	/// wakes waiters without changing the value, so they re-check predicates that depend on external state
+	pub fn notify(&self, notify: Notify) {
+		let (lock, cvar) = &self.pair;
+
+		let _data = self.recover(lock.lock()).unwrap();
+
+		self.send_notify(cvar, notify);
+	}
+
+	/// raw access to the internal mutex for patterns the rest of the API can't express;
+	/// mutate through the guard, then call `notify_one`/`notify_all` (with the guard held or
+	/// right after dropping it), otherwise waiters can miss the change. bumps `generation` while
+	/// holding the lock, so waits that watch it (`wait_for_condition_unlocked`, `stream`, ...) see the write
+	pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock());
+		self.generation.fetch_add(1, Ordering::SeqCst);
+		data
+	}
+
+	/// does not take the lock, so it may be called while holding the guard from `lock()`
+	pub fn notify_one(&self) {
+		let (_, cvar) = &self.pair;
+
+		self.send_notify(cvar, Notify::One);
+	}
+
+	/// does not take the lock, so it may be called while holding the guard from `lock()`
+	pub fn notify_all(&self) {
+		let (_, cvar) = &self.pair;
+
+		self.send_notify(cvar, Notify::All);
+	}
+
+	/// wakes every waiter and makes all waits whose condition doesn't hold fail with
+	/// `WaitError::Closed`, now and later; call it when the producing side goes away
+	pub fn close(&self) {
+		let (lock, cvar) = &self.pair;
+
+		let _data = self.recover(lock.lock());
+		self.closed.store(true, Ordering::SeqCst);
+
+		self.send_notify(cvar, Notify::All);
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed.load(Ordering::SeqCst)
+	}
+
+	/// makes every writer reject its write until `thaw`, reporting it through its return value
+	/// (`false`, `None`, `Err`); waiters keep working on the frozen value. takes the lock, so no write
+	/// lands after it returns. only raw guards, from `lock()` or the `_locked` waits, can still write
+	pub fn freeze(&self) {
+		let (lock, _) = &self.pair;
+
+		let _data = self.recover(lock.lock());
+		self.frozen.store(true, Ordering::SeqCst);
+	}
+
+	pub fn thaw(&self) {
+		self.frozen.store(false, Ordering::SeqCst);
+	}
+
+	pub fn is_frozen(&self) -> bool {
+		self.frozen.load(Ordering::SeqCst)
+	}
+
+	/// registers `f` to be called with the new value on every write, in registration order;
+	/// it runs while the lock is held, so calling back into this condition variable deadlocks
+	pub fn subscribe<F:Fn(&T) + Send + 'static>(&self, f: F) {
+		self.subscribe_while(move |data| {
+			f(data);
+			true
+		});
+	}
+
+	/// like `subscribe`, but `f` is dropped the first time it returns `false`
+	pub(crate) fn subscribe_while<F:Fn(&T) -> bool + Send + 'static>(&self, f: F) {
+		self.recover(self.subscribers.lock()).unwrap().push(Box::new(f));
+	}
+
+	/// passes lock results through, or strips the poison from them with `new_ignoring_poison`
+	fn recover<G>(&self, res: LockResult<G>) -> LockResult<G> {
+		match res {
+			Err(poison) if self.ignore_poison => Ok(poison.into_inner()),
+			res => res,
+		}
+	}
+
+	/// a single blocking wait on the condvar; see `attach_cancel` for when it fails
+	fn block<'a>(&self, cvar: &Condvar, mut guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>, WaitError> {
+		self.relay(cvar);
+
+		let fair = match &self.fair {
+			Some(fair) => fair,
+			None => return self.block_once(cvar, guard),
+		};
+		let lock_fair = || fair.lock().unwrap_or_else(|poison| poison.into_inner());
+
+		let (ticket, broadcasts) = lock_fair().enqueue();
+		loop {
+			guard = match self.block_once(cvar, guard) {
+				Ok(guard) => guard,
+				Err(err) => {
+					lock_fair().leave(ticket);
+					return Err(err);
+				},
+			};
+			if lock_fair().try_release(ticket, broadcasts) {
+				return Ok(guard);
+			}
+		}
+	}
+
+	/// called by a waiter about to sleep because its predicate failed, which may have been woken by a
+	/// `set_one_safe` meant for someone else. a hop only moves the original notify along, so it
+	/// neither counts for `stats` nor as a notify for the notify-only waits
+	fn relay(&self, cvar: &Condvar) {
+		if self.relay.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |hops| hops.checked_sub(1)).is_ok() {
+			self.wake(cvar, Notify::One);
+		}
+	}
+
+	fn block_once<'a>(&self, cvar: &Condvar, guard: MutexGuard<'a, T>) -> Result<MutexGuard<'a, T>, WaitError> {
+		#[cfg(feature = "std")]
+		return self.block_timeout(cvar, guard, None);
+
+		#[cfg(not(feature = "std"))]
+		Ok(self.recover(cvar.wait(guard))?)
+	}
+
+	fn changed(&self, cvar: &Condvar, data: &T, notify: Notify) {
+		// wraps on overflow, see `newer_generation`
+		let generation = self.generation.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
+		// hops left over from an earlier `set_one_safe` were for a value that is gone now
+		self.relay.store(0, Ordering::SeqCst);
+
+		if let Some(history) = &self.history {
+			history.record(generation, data);
+		}
+
+		#[cfg(feature = "tracing")]
+		match self.debug_value {
+			Some(debug_fn) => tracing::trace!(?notify, waiters = self.waiter_count(), value = ?DebugWith(data, debug_fn), "set"),
+			None => tracing::trace!(?notify, waiters = self.waiter_count(), "set"),
+		}
+
+		self.recover(self.subscribers.lock()).unwrap().retain(|subscriber| subscriber(data));
+
+		self.send_notify(cvar, notify);
+	}
+
+	fn send_notify(&self, cvar: &Condvar, notify: Notify) {
+		if notify != Notify::None {
+			self.notifications.fetch_add(1, Ordering::SeqCst);
+		}
+		match notify {
+			Notify::None => {},
+			Notify::One => { self.notify_one_count.fetch_add(1, Ordering::Relaxed); },
+			Notify::Many(n) => { self.notify_one_count.fetch_add(n.min(self.waiter_count()) as u64, Ordering::Relaxed); },
+			Notify::All => { self.notify_all_count.fetch_add(1, Ordering::Relaxed); },
+		}
+
+		self.wake(cvar, notify);
+
+		#[cfg(feature = "async")]
+		if notify != Notify::None {
+			for waker in self.wakers.lock().unwrap().drain(..) {
+				waker.wake();
+			}
+		}
+	}
+
+	/// wakes blocked threads, without any of `send_notify`'s bookkeeping
+	fn wake(&self, cvar: &Condvar, notify: Notify) {
+		match (&self.fair, notify) {
+			(_, Notify::None) => {},
+			(Some(fair), notify) => {
+				fair.lock().unwrap_or_else(|poison| poison.into_inner()).notify(notify);
+				cvar.notify_all();
+			},
+			(None, Notify::One) => cvar.notify_one(),
+			(None, Notify::All) => cvar.notify_all(),
+			// never more than there are waiters to wake, this runs under the data lock
+			(None, Notify::Many(n)) => for _ in 0..n.min(self.waiter_count()) { cvar.notify_one() },
+		}
+	}
+
+	/// parks an async poll until the next notify; call it while still holding the data lock,
+	/// so a concurrent set can't slip by between the check and the registration
+	#[cfg(feature = "async")]
+	pub(crate) fn register_waker(&self, waker: &Waker) {
+		let mut wakers = self.wakers.lock().unwrap();
+		if !wakers.iter().any(|w| w.will_wake(waker)) {
+			wakers.push(waker.clone());
+		}
+	}
+
+	/// the primitive read: runs `f` on the value under the lock, so `T` needn't be `Clone`
+	pub fn peek<R, F:FnOnce(&T) -> R>(&self, f: F) -> Result<R, PoisonError<MutexGuard<'_, T>>> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock())?;
+
+		Ok(f(&*data))
+	}
+
+	/// runs `f` on the value under the lock, without cloning it; same as `peek`
+	pub fn with_lock<R, F:FnOnce(&T) -> R>(&self, f: F) -> Result<R, PoisonError<MutexGuard<'_, T>>> {
+		self.peek(f)
+	}
+
+	/// like `wait_for_condition`, but returns the still-held guard
+	pub fn wait_for_condition_locked<F:FnMut(&T) -> bool>(&self, mut cond_func: F)
+		-> Result<MutexGuard<'_, T>, WaitError>
+	{
+		let (lock, cvar) = &self.pair;
+		let mut actual = self.recover(lock.lock())?;
+
+		// fast path: already true, so no waiter bookkeeping and no condvar
+		if cond_func(&*actual) {
+			self.count_wait();
+			return Ok(actual);
+		}
+
+		let _waiting = WaiterGuard::new(self);
+		#[cfg(feature = "tracing")]
+		tracing::trace!(waiters = self.waiter_count(), "waiting");
+
+		loop {
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			actual = self.block(cvar, actual)?;
+			#[cfg(feature = "tracing")]
+			tracing::trace!(waiters = self.waiter_count(), "woke");
+
+			if cond_func(&*actual) {
+				return Ok(actual);
+			}
+		}
+	}
+
+	/// like `wait_for_condition_locked`, but `cond_func` gets `&mut T` so it can check and consume in
+	/// one lock hold, e.g. pop an item once the queue is non-empty; its changes notify no one
+	pub fn wait_for_condition_mut<F:FnMut(&mut T) -> bool>(&self, mut cond_func: F) -> Result<(), WaitError> {
+		let (lock, cvar) = &self.pair;
+		let mut actual = self.recover(lock.lock())?;
+
+		// fast path, see `wait_for_condition_locked`
+		if cond_func(&mut *actual) {
+			self.count_wait();
+			return Ok(());
+		}
+
+		let _waiting = WaiterGuard::new(self);
+		#[cfg(feature = "tracing")]
+		tracing::trace!(waiters = self.waiter_count(), "waiting");
+
+		loop {
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			actual = self.block(cvar, actual)?;
+			#[cfg(feature = "tracing")]
+			tracing::trace!(waiters = self.waiter_count(), "woke");
+
+			if cond_func(&mut *actual) {
+				return Ok(());
+			}
+		}
+	}
+
+	/// debugging safety valve for a predicate that never settles: gives up with `Ok(false)` after
+	/// `max_wakeups` wakeups (spurious ones included) without `cond_func` holding
+	pub fn wait_for_condition_bounded<F:FnMut(&T) -> bool>(&self, mut cond_func: F, max_wakeups: u32)
+		-> Result<bool, WaitError>
+	{
+		let (lock, cvar) = &self.pair;
+		let mut actual = self.recover(lock.lock())?;
+
+		// fast path, see `wait_for_condition_locked`
+		if cond_func(&*actual) {
+			self.count_wait();
+			return Ok(true);
+		}
+
+		let _waiting = WaiterGuard::new(self);
+		let mut wakeups = 0;
+		loop {
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			if wakeups == max_wakeups {
+				return Ok(false);
+			}
+			actual = self.block(cvar, actual)?;
+			wakeups += 1;
+
+			if cond_func(&*actual) {
+				return Ok(true);
+			}
+		}
+	}
+
+	/// like `wait_for_condition_bounded`, but caps evaluations of `cond_func` (the first check plus one
+	/// per wakeup) instead of wakeups, for predicates with a cost or side effects; `Ok(false)` once
+	/// `max_evals` checks failed, so 0 never calls it
+	pub fn wait_for_condition_limited<F:FnMut(&T) -> bool>(&self, cond_func: F, max_evals: u32)
+		-> Result<bool, WaitError>
+	{
+		match max_evals {
+			0 => Ok(false),
+			n => self.wait_for_condition_bounded(cond_func, n - 1),
+		}
+	}
+
+	/// waits until at least `n` more writes happened than when it was called; returns the generation
+	pub fn wait_for_n_updates(&self, n: u64) -> Result<u64, WaitError> {
+		let start = self.generation();
+
+		let _data = self.wait_for_condition_locked(|_| self.generation().wrapping_sub(start) >= n)?;
+
+		Ok(self.generation())
+	}
+
+	/// for predicates that can fail, e.g. parse the value: an `Err` from `cond_func` ends the wait and is
+	/// returned in the inner `Result`, kept apart from the wait's own errors in the outer one
+	pub fn wait_for_try_condition<E, F:FnMut(&T) -> Result<bool, E>>(&self, mut cond_func: F)
+		-> Result<Result<(), E>, WaitError>
+	{
+		let mut error = None;
+		let _data = self.wait_for_condition_locked(|actual| match cond_func(actual) {
+			Ok(held) => held,
+			Err(err) => {
+				error = Some(err);
+				true
+			},
+		})?;
+
+		Ok(match error {
+			Some(err) => Err(err),
+			None => Ok(()),
+		})
+	}
+
+	/// checks whether `cond_func` currently holds, without ever waiting on the condvar
+	pub fn try_wait_for<F:FnMut(&T) -> bool>(&self, cond_func: F) -> Result<bool, PoisonError<MutexGuard<'_, T>>> {
+		self.with_lock(cond_func)
+	}
+
+	/// timed wait for targets without `std::time`: `now` reports the time elapsed on any monotonic
+	/// clock; the lock is released between polls, but the caller's thread spins instead of sleeping
+	pub fn wait_for_condition_with_clock<F:FnMut(&T) -> bool, C:Fn() -> Duration>(&self, mut cond_func: F,
+		timeout: Duration, now: C) -> Result<WaitResult, WaitError>
+	{
+		let (lock, _) = &self.pair;
+		let _waiting = WaiterGuard::new(self);
+		let start = now();
+
+		loop {
+			if cond_func(&*self.recover(lock.lock())?) {
+				return Ok(WaitResult::Satisfied);
+			}
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			#[cfg(feature = "std")]
+			if self.cancel_requested() {
+				return Err(WaitError::Cancelled);
+			}
+			if now().saturating_sub(start) >= timeout {
+				self.timed_out();
+				return Ok(WaitResult::TimedOut);
+			}
+			spin_loop();
+		}
+	}
+
+	/// waits until `getter` projects the value onto `expected`, e.g. a single field of a struct
+	pub fn wait_for_projection<U:PartialEq, G:Fn(&T) -> U>(&self, getter: G, expected: U)
+		-> Result<(), WaitError>
+	{
+		self.wait_for_condition_locked(|actual| getter(actual) == expected).map(|_| ())
+	}
+}
+
+impl<T:Clone> ConditionVariable<T> {
+	/// keeps a clone of `value` so `reset` can restore it, e.g. to re-arm a latch
+	pub fn new_resettable(value: T) -> ConditionVariable<T> {
+		ConditionVariable {
+			initial: Some(Mutex::new(value.clone())),
+			..ConditionVariable::new(value)
+		}
+	}
+
+	/// sets the value back to the one passed to `new_resettable`; returns `false` and leaves
+	/// the value alone if the condition variable was constructed any other way, or while frozen
+	pub fn reset(&self, notify: Notify) -> bool {
+		match &self.initial {
+			Some(initial) => {
+				let initial = initial.lock().unwrap_or_else(|poison| poison.into_inner()).clone();
+				self.set(initial, notify)
+			},
+			None => false,
+		}
+	}
+
+	pub fn get(&self) -> Result<T, PoisonError<MutexGuard<'_, T>>> {
+		self.peek(T::clone)
+	}
+
+	/// returns the value even if the lock is poisoned (poisoning is ignored!)
+	pub fn get_or_recover(&self) -> T {
+		let (lock, _) = &self.pair;
+
+		match lock.lock() {
+			Ok(data) => data.clone(),
+			Err(poison) => poison.into_inner().clone(),
+		}
+	}
+
+	/// like `update`, returning clones of the value from before and after `f`, for audit logs;
+	/// `None` while frozen
+	pub fn update_reporting<F:FnOnce(&mut T)>(&self, f: F, notify: Notify) -> Option<(T, T)> {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return None;
+		}
+		let old = data.clone();
+		f(&mut *data);
+
+		self.changed(cvar, &*data, notify);
+		Some((old, data.clone()))
+	}
+
+	/// returns `fallback` instead of an error if the lock is poisoned, e.g. for status displays
+	pub fn get_or(&self, fallback: T) -> T {
+		self.get().unwrap_or(fallback)
+	}
+
+	/// like `AtomicUsize::fetch_update`: stores `f`'s `Some(new)` and returns `Ok(old)`,
+	/// or leaves the value alone and returns `Err(current)` without notifying if `f` gives `None`,
+	/// or without calling `f` while frozen
+	pub fn fetch_update<F:FnMut(&T) -> Option<T>>(&self, mut f: F, notify: Notify) -> Result<T, T> {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return Err(data.clone());
+		}
+		let new = match f(&*data) {
+			Some(new) => new,
+			None => return Err(data.clone()),
+		};
+		let previous = mem::replace(&mut *data, new);
+
+		self.changed(cvar, &*data, notify);
+		Ok(previous)
+	}
+
+	/// returns `None` instead of blocking if the lock is currently held
+	pub fn try_get(&self) -> Option<T> {
+		let (lock, _) = &self.pair;
+
+		match lock.try_lock() {
+			Ok(data) => Some(data.clone()),
+			Err(TryLockError::Poisoned(poison)) => Some(poison.into_inner().clone()),
+			Err(TryLockError::WouldBlock) => None,
+		}
+	}
+
+	/// for hand-rolled event loops: `Ready` with a clone of the value if `cond_func` holds right now,
+	/// `Pending` otherwise, never waiting; re-poll from a `subscribe` callback to learn about changes.
+	/// reads through poisoning like `try_get`
+	pub fn poll_condition<F:FnMut(&T) -> bool>(&self, mut cond_func: F) -> Poll<T> {
+		let (lock, _) = &self.pair;
+
+		let data = lock.lock().unwrap_or_else(|poison| poison.into_inner());
+		if cond_func(&*data) {
+			Poll::Ready(data.clone())
+		} else {
+			Poll::Pending
+		}
+	}
+
+	/// returns a clone of the value that satisfied `cond_func`
+	pub fn wait_for_condition<F:FnMut(&T) -> bool>(&self, cond_func: F) -> Result<T, WaitError> {
+		let actual = self.wait_for_condition_locked(cond_func)?;
+
+		Ok(actual.clone())
+	}
+
+	/// `wait_for_condition` under a name for waiting on an enum variant whatever its payload, which
+	/// `wait_for` can't do and which needs no `PartialEq`:
+	/// `cvar.wait_for_matching(|state| matches!(state, State::Ready { .. }))`
+	pub fn wait_for_matching<F:FnMut(&T) -> bool>(&self, pattern: F) -> Result<T, WaitError> {
+		self.wait_for_condition(pattern)
+	}
+
+	/// waits until a cheap tag of the value, e.g. a version field, equals `expected`, so the payload
+	/// itself is never compared; returns a clone of the whole value. see also `wait_for_projection`
+	pub fn wait_for_version<V:PartialEq, G:Fn(&T) -> V>(&self, tag: G, expected: V) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| tag(actual) == expected)
+	}
+
+	/// like `wait_for_condition_locked`, also returning a clone of the satisfying value, so the caller
+	/// keeps that snapshot while mutating through the still-held guard
+	pub fn wait_for_value_locked<F:FnMut(&T) -> bool>(&self, cond_func: F)
+		-> Result<(MutexGuard<'_, T>, T), WaitError>
+	{
+		let actual = self.wait_for_condition_locked(cond_func)?;
+		let value = actual.clone();
+
+		Ok((actual, value))
+	}
+
+	/// for expensive predicates: evaluates `cond_func` on a clone with the lock released, so setters
+	/// aren't blocked meanwhile. the clone may be stale by the time the check finishes, so this is only
+	/// correct for monotonic predicates (once true, true for every later value). no write is missed:
+	/// it only sleeps if nothing was written since the clone was taken
+	pub fn wait_for_condition_unlocked<F:FnMut(&T) -> bool>(&self, mut cond_func: F) -> Result<T, WaitError> {
+		let (lock, cvar) = &self.pair;
+		let _waiting = WaiterGuard::new(self);
+
+		loop {
+			let actual = self.recover(lock.lock())?;
+			let (snapshot, seen) = (actual.clone(), self.generation());
+			drop(actual);
+
+			if cond_func(&snapshot) {
+				return Ok(snapshot);
+			}
+
+			let mut actual = self.recover(lock.lock())?;
+			while self.generation() == seen {
+				if self.is_closed() {
+					return Err(WaitError::Closed);
+				}
+				actual = self.block(cvar, actual)?;
+			}
+		}
+	}
+
+	/// request/response handoff: writes the request, notifies all and waits for `reply_cond`, without
+	/// releasing the lock in between, so the reply can't slip past before the wait starts.
+	/// `reply_cond` must not hold for the request itself, or it returns right away.
+	/// fails with `WaitError::Frozen`, without writing or waiting, while frozen
+	pub fn set_and_wait_for<F:FnMut(&T) -> bool>(&self, value: T, mut reply_cond: F) -> Result<T, WaitError> {
+		let (lock, cvar) = &self.pair;
+		let mut actual = self.recover(lock.lock())?;
+		if self.is_frozen() {
+			return Err(WaitError::Frozen);
+		}
+		*actual = value;
+		self.changed(cvar, &*actual, Notify::All);
+
+		// fast path, see `wait_for_condition_locked`
+		if reply_cond(&*actual) {
+			self.count_wait();
+			return Ok(actual.clone());
+		}
+
+		let _waiting = WaiterGuard::new(self);
+		loop {
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			actual = self.block(cvar, actual)?;
+
+			if reply_cond(&*actual) {
+				return Ok(actual.clone());
+			}
+		}
+	}
+
+	/// checks `cond_func` up to `spin_iters` times, re-taking the lock each time, before blocking in
+	/// `wait_for_condition`; lower latency for flags that flip quickly, paid for with busy CPU time,
+	/// so keep `spin_iters` small (tens to hundreds) unless the flip is known to be imminent
+	pub fn wait_for_condition_spin<F:FnMut(&T) -> bool>(&self, mut cond_func: F, spin_iters: usize) -> Result<T, WaitError> {
+		let (lock, _) = &self.pair;
+
+		for _ in 0..spin_iters {
+			let actual = self.recover(lock.lock())?;
+			if cond_func(&*actual) {
+				return Ok(actual.clone());
+			}
+			drop(actual);
+
+			spin_loop();
+		}
+
+		self.wait_for_condition(cond_func)
+	}
+
+	/// waits until there was a write after generation `seen`, even if it restored an earlier value;
+	/// returns the new generation along with the value
+	pub fn wait_for_generation_after(&self, seen: u64) -> Result<(u64, T), WaitError> {
+		let actual = self.wait_for_condition_locked(|_| newer_generation(self.generation(), seen))?;
+
+		Ok((self.generation(), actual.clone()))
+	}
+
+	/// like `wait_for_condition` with a borrowed trait object, so stored predicates can be reused
+	pub fn wait_for_condition_ref(&self, cond_func: &dyn Fn(&T) -> bool) -> Result<T, WaitError> {
+		self.wait_for_condition(cond_func)
+	}
+
+	/// returns the value right away if `cond_func` already holds, otherwise waits for it;
+	/// the check and the wait happen under the same lock acquisition, so no notify is missed
+	pub fn get_or_wait<F:FnMut(&T) -> bool>(&self, cond_func: F) -> Result<T, WaitError> {
+		self.wait_for_condition(cond_func)
+	}
+
+	/// waits as long as `cond_func` holds (the inverse of `wait_for_condition`)
+	pub fn wait_while<F:FnMut(&T) -> bool>(&self, mut cond_func: F) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| !cond_func(actual))
+	}
+}
+
+impl<T:PartialEq> ConditionVariable<T> {
+	/// waits for each value of `sequence` in order; after the first, each must come from a later
+	/// write than the previous match, so `[A, A]` needs two writes of `A`.
+	/// writes coalesce like for every wait: a state that is set and replaced before this thread
+	/// wakes up is never seen, and the wait then blocks until the state comes around again
+	pub fn wait_for_each(&self, sequence: &[T]) -> Result<(), WaitError> {
+		let mut matched: Option<u64> = None;
+
+		for target in sequence {
+			let _data = self.wait_for_condition_locked(|actual| {
+				*actual == *target && matched.is_none_or(|generation| newer_generation(self.generation(), generation))
+			})?;
+			matched = Some(self.generation());
+		}
+
+		Ok(())
+	}
+
+	/// replaces the value with `new` if it currently equals `current`; returns whether it did
+	pub fn compare_and_set(&self, current: T, new: T, notify: Notify) -> bool {
+		self.set_if(new, |actual| *actual == current, notify)
+	}
+
+	/// only writes and notifies if `value` differs from the current value; returns whether it did
+	pub fn set_if_changed(&self, value: T, notify: Notify) -> bool {
+		let (lock, cvar) = &self.pair;
+
+		let mut data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() || *data == value {
+			return false;
+		}
+		*data = value;
+
+		self.changed(cvar, &*data, notify);
+		true
+	}
+
+}
+
+impl<T:PartialEq+Clone> ConditionVariable<T> {
+	pub fn wait_for(&self, expected: T) -> Result<T, WaitError> {
+		self.wait_for_in(&[expected])
+	}
+
+	pub fn wait_for_in(&self, expected: &[T]) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| expected.contains(actual))
+	}
+
+	/// like `wait_for_in` for sets that aren't a slice, e.g. a range; `expected` is cloned and
+	/// walked on every check, so it should be cheap to clone
+	pub fn wait_for_in_iter<I>(&self, expected: I) -> Result<T, WaitError>
+		where I: IntoIterator<Item=T> + Clone
+	{
+		self.wait_for_condition(|actual| expected.clone().into_iter().any(|e| e == *actual))
+	}
+
+	/// waits until the value equals one of `expected` and returns the entry that matched
+	pub fn wait_for_any(&self, expected: &[T]) -> Result<T, WaitError> {
+		let actual = self.wait_for_condition_locked(|actual| expected.contains(actual))?;
+
+		Ok(expected.iter().find(|e| **e == *actual).unwrap().clone())
+	}
+
+	/// debugging aid: like `wait_for_condition`, but fails with `WaitError::SuspectedLivelock` after
+	/// `threshold` wakeups in a row that found the value equal to before and `cond_func` still false,
+	/// e.g. a producer that keeps setting the same value. clones the value whenever it changed
+	pub fn wait_for_condition_detect_livelock<F:FnMut(&T) -> bool>(&self, mut cond_func: F, threshold: u32)
+		-> Result<T, WaitError>
+	{
+		let (lock, cvar) = &self.pair;
+		let mut actual = self.recover(lock.lock())?;
+
+		// fast path, see `wait_for_condition_locked`
+		if cond_func(&*actual) {
+			self.count_wait();
+			return Ok(actual.clone());
+		}
+
+		let _waiting = WaiterGuard::new(self);
+		let mut last = actual.clone();
+		let mut unchanged = 0;
+		loop {
+			if unchanged >= threshold {
+				return Err(WaitError::SuspectedLivelock);
+			}
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			actual = self.block(cvar, actual)?;
+
+			if cond_func(&*actual) {
+				return Ok(actual.clone());
+			}
+			if *actual == last {
+				unchanged += 1;
+			} else {
+				unchanged = 0;
+				last = actual.clone();
+			}
+		}
+	}
+
+	/// waits until the value differs from `last` and returns the new value
+	pub fn wait_for_change(&self, last: T) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| *actual != last)
+	}
+
+	/// waits until the value is not equal to `unwanted` and returns it
+	pub fn wait_for_ne(&self, unwanted: T) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| *actual != unwanted)
+	}
+
+}
+
+#[cfg(feature = "std")]
+impl<T:Hash+Eq+Clone> ConditionVariable<T> {
+	/// like `wait_for_in`, with an O(1) membership check per wakeup for large sets
+	pub fn wait_for_in_set<S:BuildHasher>(&self, expected: &HashSet<T, S>) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| expected.contains(actual))
+	}
+}
+
+impl<T:Copy> ConditionVariable<T> {
+	/// like `get`, but copies the value out for `Copy` types such as flags and counters
+	pub fn get_copy(&self) -> Result<T, PoisonError<MutexGuard<'_, T>>> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock())?;
+
+		Ok(*data)
+	}
+}
+
+impl ConditionVariable<()> {
+	/// the producer side of `wait_ms`/`wait_timeout`: wakes waiters, there is no value to set
+	pub fn signal(&self, notify: Notify) {
+		self.notify(notify);
+	}
+}
+
+/// sets every condition variable in `cvars` to a clone of `value`, for fan-out signaling;
+/// not atomic across the group: each one is set under its own lock, one after the other
+pub fn set_all<T:Clone>(cvars: &[&ConditionVariable<T>], value: T, notify: Notify) {
+	for cvar in cvars {
+		cvar.set(value.clone(), notify);
+	}
+}
+
+impl<T:Default> Default for ConditionVariable<T> {
+	fn default() -> ConditionVariable<T> {
+		ConditionVariable::new(T::default())
+	}
+}
+
+/// never blocks: prints `<locked>` if the lock is held and `<poisoned>` if it is poisoned
+impl<T:fmt::Debug> fmt::Debug for ConditionVariable<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let (lock, _) = &self.pair;
+
+		let mut d = f.debug_struct("ConditionVariable");
+		match lock.try_lock() {
+			Ok(data) => d.field("value", &*data),
+			Err(TryLockError::Poisoned(_)) => d.field("value", &format_args!("<poisoned>")),
+			Err(TryLockError::WouldBlock) => d.field("value", &format_args!("<locked>")),
+		};
+		d.finish()
+	}
+}
+
+impl<T:PartialEq+fmt::Debug> ConditionVariable<T> {
+	/// test helper: panics showing both values if the current value isn't `expected`;
+	/// like `debug_assert_eq!`, a no-op without `debug_assertions`
+	pub fn debug_assert_value(&self, expected: &T) {
+		if cfg!(debug_assertions) {
+			let (lock, _) = &self.pair;
+
+			let actual = lock.lock().unwrap_or_else(|poison| poison.into_inner());
+			assert_eq!(*actual, *expected, "unexpected ConditionVariable value");
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::collections::{HashSet, VecDeque};
+	use std::sync::{Arc, Mutex};
+	use std::sync::atomic::{AtomicBool, Ordering};
+	use std::task::Poll;
+	use std::thread::{sleep, spawn};
+	use std::time::{Duration, Instant};
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+	use crate::Clock;
+	use crate::WaitError;
+	use crate::WaitResult;
+	use crate::WaitOutcome;
+	use crate::Budget;
+	use crate::CvStats;
+
+	struct FakeClock(Arc<Mutex<Instant>>);
+
+	impl Clock for FakeClock {
+		fn now(&self) -> Instant {
+			*self.0.lock().unwrap()
+		}
+	}
+
+	#[cfg(not(feature = "parking_lot"))]
+	fn poison<T:Clone+Send+'static>(cvar: &Arc<ConditionVariable<T>>) {
+		let cvar = cvar.clone();
+
+		let res = spawn(move || {
+			let _ = cvar.wait_for_condition(|_| panic!("poisoning the lock"));
+		}).join();
+		assert!(res.is_err());
+	}
+
+	#[test]
+	fn test_wait_for() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set(true, Notify::All);
+		});
+
+		cvar1.wait_for(true).unwrap();
+	}
+
+	#[test]
+	fn test_wait_for_in_returns_value() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set(2, Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_in(&[1, 2]).unwrap(), 2);
+	}
+
+	#[test]
+	fn test_update() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			for _ in 0..3 {
+				cvar2.update(|v| *v += 1, Notify::All);
+			}
+		});
+
+		cvar1.wait_for(3).unwrap();
+	}
+
+	#[test]
+	fn test_wait_for_ms() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(500));
+			cvar2.set(true, Notify::All);
+		});
+
+		assert!(cvar1.wait_for_ms(true, 1000).unwrap().result.is_satisfied());
+	}
+
+	#[test]
+	fn test_wait_for_ms_fail() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(1000));
+			cvar2.set(true, Notify::All);
+		});
+
+		assert!(!cvar1.wait_for_ms(true, 500).unwrap().result.is_satisfied());
+	}
+
+	#[test]
+	fn test_wait_for_timeout() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(500));
+			cvar2.set(true, Notify::All);
+		});
+
+		assert_eq!(cvar1.timed_wait_for(true, Duration::from_millis(1000)).map(|res| res.result), Ok(WaitResult::Satisfied));
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_wait_for_condition_ms_poisoned() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		poison(&cvar1);
+
+		assert_eq!(cvar1.wait_for_condition_ms(|actual| *actual, 100), Err(WaitError::Poisoned));
+	}
+
+	#[test]
+	fn test_set_if() {
+		let cvar = ConditionVariable::new(1);
+
+		assert!(!cvar.set_if(3, |v| *v == 2, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 1);
+
+		assert!(cvar.set_if(3, |v| *v == 1, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 3);
+	}
+
+	#[test]
+	fn test_compare_and_set() {
+		let cvar = ConditionVariable::new(1);
+
+		assert!(!cvar.compare_and_set(2, 3, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 1);
+
+		assert!(cvar.compare_and_set(1, 3, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 3);
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_get_or_recover() {
+		let cvar = Arc::new(ConditionVariable::new(42));
+		poison(&cvar);
+
+		assert!(cvar.get().is_err());
+		assert_eq!(cvar.get_or_recover(), 42);
+	}
+
+	#[test]
+	fn test_try_get() {
+		let cvar1 = Arc::new(ConditionVariable::new(42));
+		let cvar2 = cvar1.clone();
+
+		assert_eq!(cvar1.try_get(), Some(42));
+
+		let guard = cvar1.pair.0.lock().unwrap();
+		let res = spawn(move || cvar2.try_get()).join().unwrap();
+		assert_eq!(res, None);
+		drop(guard);
+	}
+
+	#[test]
+	fn test_wait_for_change() {
+		let cvar1 = Arc::new(ConditionVariable::new(1));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(100));
+			cvar2.set(2, Notify::All);
+		});
+
+		let last = cvar1.get().unwrap();
+		assert_eq!(cvar1.wait_for_change(last).unwrap(), 2);
+	}
+
+	#[test]
+	fn test_wait_while() {
+		let cvar1 = Arc::new(ConditionVariable::new(true));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(100));
+			cvar2.set(false, Notify::All);
+		});
+
+		assert!(!cvar1.wait_while_ms(|busy| *busy, 10).unwrap().result.is_satisfied());
+		assert!(!cvar1.wait_while(|busy| *busy).unwrap());
+	}
+
+	#[test]
+	fn test_with_lock() {
+		let cvar = ConditionVariable::new(vec![1, 2, 3]);
+
+		assert_eq!(cvar.with_lock(|v| v.len()).unwrap(), 3);
+	}
+
+	#[test]
+	fn test_notify_many() {
+		let cvar = Arc::new(ConditionVariable::new(false));
+
+		let waiters: Vec<_> = (0..2).map(|_| {
+			let cvar = cvar.clone();
+			spawn(move || cvar.wait_for(true).unwrap())
+		}).collect();
+
+		sleep(Duration::from_millis(100));
+		cvar.set(true, Notify::Many(2));
+
+		for w in waiters {
+			assert!(w.join().unwrap());
+		}
+	}
+
+	#[test]
+	fn test_without_partial_eq() {
+		struct Opaque(u32);
+
+		let cvar = ConditionVariable::new(Opaque(1));
+		cvar.set(Opaque(2), Notify::All);
+		cvar.update(|v| v.0 += 1, Notify::All);
+
+		assert_eq!(cvar.with_lock(|v| v.0).unwrap(), 3);
+	}
+
+	#[test]
+	fn test_wait_for_condition_ms_honors_timeout() {
+		let cvar = ConditionVariable::new(false);
+
+		let before = Instant::now();
+		assert!(!cvar.wait_for_condition_ms(|actual| *actual, 200).unwrap().result.is_satisfied());
+
+		let elapsed = before.elapsed();
+		assert!(elapsed >= Duration::from_millis(200));
+		assert!(elapsed < Duration::from_millis(300));
+	}
+
+	#[test]
+	fn test_wait_for_condition_deadline() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(100));
+			cvar2.set(1, Notify::All);
+		});
+
+		let deadline = Instant::now() + Duration::from_millis(500);
+		let res = cvar1.wait_for_condition_deadline(|actual| *actual == 1, deadline).unwrap();
+		assert!(res.result.is_satisfied());
+		assert!(res.remaining > Duration::from_millis(0));
+
+		let res = cvar1.wait_for_condition_deadline(|actual| *actual == 2, deadline).unwrap();
+		assert!(!res.result.is_satisfied());
+		assert_eq!(res.remaining, Duration::from_millis(0));
+	}
+
+	#[test]
+	fn test_set_and_get() {
+		let cvar = ConditionVariable::new(1);
+
+		assert_eq!(cvar.set_and_get(2, Notify::All), Ok(1));
+		assert_eq!(cvar.set_and_get(3, Notify::All), Ok(2));
+		assert_eq!(cvar.get().unwrap(), 3);
+	}
+
+	#[test]
+	fn test_into_inner() {
+		let cvar = ConditionVariable::new(vec![1, 2]);
+		cvar.update(|v| v.push(3), Notify::All);
+
+		assert_eq!(cvar.into_inner().unwrap(), vec![1, 2, 3]);
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_into_inner_poisoned() {
+		let cvar = Arc::new(ConditionVariable::new(1));
+		poison(&cvar);
+
+		let cvar = Arc::try_unwrap(cvar).ok().unwrap();
+		assert_eq!(cvar.into_inner().unwrap_err().into_inner(), 1);
+	}
+
+	#[test]
+	fn test_get_mut() {
+		let mut cvar = ConditionVariable::new(vec![1]);
+		cvar.get_mut().unwrap().push(2);
+
+		assert_eq!(cvar.get().unwrap(), vec![1, 2]);
+	}
+
+	#[test]
+	fn test_default() {
+		let cvar: ConditionVariable<u32> = Default::default();
+
+		assert_eq!(cvar.get().unwrap(), 0);
+	}
+
+	#[test]
+	fn test_set_if_changed() {
+		let cvar = ConditionVariable::new(1);
+
+		assert!(!cvar.set_if_changed(1, Notify::All));
+		assert!(cvar.set_if_changed(2, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_wait_for_condition_locked() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set(1, Notify::All);
+		});
+
+		let mut guard = cvar1.wait_for_condition_locked(|actual| *actual == 1).unwrap();
+		*guard = 2;
+		drop(guard);
+
+		assert_eq!(cvar1.get().unwrap(), 2);
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_wait_error_boxed() {
+		fn wait(cvar: &ConditionVariable<bool>) -> Result<bool, Box<dyn std::error::Error>> {
+			Ok(cvar.wait_for_ms(true, 10)?.result.is_satisfied())
+		}
+
+		let cvar = Arc::new(ConditionVariable::new(true));
+		assert!(wait(&cvar).unwrap());
+
+		poison(&cvar);
+		assert_eq!(wait(&cvar).unwrap_err().to_string(), "lock poisoned by a panicking thread");
+	}
+
+	#[test]
+	#[cfg(feature = "parking_lot")]
+	fn test_never_poisons() {
+		let cvar1 = Arc::new(ConditionVariable::new(1));
+		let cvar2 = cvar1.clone();
+
+		let res = spawn(move || {
+			let _ = cvar2.wait_for_condition(|_| panic!("panicking while holding the lock"));
+		}).join();
+		assert!(res.is_err());
+
+		assert_eq!(cvar1.get().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_waiter_count() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+		let cvar3 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.wait_for(true).unwrap());
+		let timed = spawn(move || cvar3.wait_for_ms(true, 100).unwrap());
+
+		sleep(Duration::from_millis(50));
+		assert_eq!(cvar1.waiter_count(), 2);
+
+		timed.join().unwrap();
+		assert_eq!(cvar1.waiter_count(), 1);
+
+		cvar1.set(true, Notify::All);
+		waiter.join().unwrap();
+		assert_eq!(cvar1.waiter_count(), 0);
+	}
+
+	#[test]
+	fn test_builder() {
+		let cvar1 = Arc::new(ConditionVariable::builder()
+			.value(false)
+			.default_notify(Notify::One)
+			.build());
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set_default(true);
+		});
+
+		assert!(cvar1.wait_for(true).unwrap());
+	}
+
+	#[test]
+	fn test_modify() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			for _ in 0..5 {
+				let v = cvar2.modify(|v| {
+					*v += 1;
+					(*v >= 3, *v)
+				}).unwrap();
+				assert!(v <= 5);
+			}
+		});
+
+		assert!(cvar1.wait_for_condition(|v| *v >= 3).unwrap() >= 3);
+	}
+
+	#[test]
+	fn test_wait_for_ne() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		assert!(!cvar1.wait_for_ne_ms(0, 10).unwrap().result.is_satisfied());
+
+		spawn(move || {
+			cvar2.set(1, Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_ne(0).unwrap(), 1);
+		assert!(cvar1.wait_for_ne_ms(0, 10).unwrap().result.is_satisfied());
+	}
+
+	#[test]
+	fn test_debug() {
+		let cvar = ConditionVariable::new(42);
+		assert_eq!(format!("{:?}", cvar), "ConditionVariable { value: 42 }");
+
+		let guard = cvar.pair.0.lock().unwrap();
+		assert_eq!(format!("{:?}", cvar), "ConditionVariable { value: <locked> }");
+		drop(guard);
+	}
+
+	#[test]
+	fn test_wait_for_value_ms() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		assert_eq!(cvar1.wait_for_value_ms(1, 10).unwrap(), None);
+
+		spawn(move || {
+			cvar2.set(1, Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_value_ms(1, 1000).unwrap(), Some(1));
+	}
+
+	#[test]
+	fn test_batch_set() {
+		let cvar = ConditionVariable::new(0);
+		cvar.batch_set(vec![1, 2, 3], Notify::All);
+
+		assert_eq!(cvar.get().unwrap(), 3);
+	}
+
+	#[test]
+	fn test_subscribe() {
+		let seen = Arc::new(Mutex::new(Vec::new()));
+		let cvar = ConditionVariable::new(0);
+
+		let seen1 = seen.clone();
+		cvar.subscribe(move |v| seen1.lock().unwrap().push(("first", *v)));
+		let seen2 = seen.clone();
+		cvar.subscribe(move |v| seen2.lock().unwrap().push(("second", *v)));
+
+		cvar.set(1, Notify::All);
+		cvar.update(|v| *v += 1, Notify::All);
+
+		assert_eq!(*seen.lock().unwrap(), vec![("first", 1), ("second", 1), ("first", 2), ("second", 2)]);
+	}
+
+	#[test]
+	fn test_wait_for_projection() {
+		struct State {
+			ready: bool,
+			payload: Vec<u8>,
+		}
+
+		let cvar1 = Arc::new(ConditionVariable::new(State { ready: false, payload: vec![] }));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.update(|s| s.payload.push(1), Notify::All);
+			cvar2.update(|s| s.ready = true, Notify::All);
+		});
+
+		cvar1.wait_for_projection(|s| s.ready, true).unwrap();
+		assert_eq!(cvar1.with_lock(|s| s.payload.clone()).unwrap(), vec![1]);
+	}
+
+	#[test]
+	fn test_notify() {
+		let cvar1 = Arc::new(ConditionVariable::new(()));
+		let cvar2 = cvar1.clone();
+		let flag1 = Arc::new(AtomicBool::new(false));
+		let flag2 = flag1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(100));
+			flag2.store(true, Ordering::SeqCst);
+			cvar2.notify(Notify::All);
+		});
+
+		cvar1.wait_for_condition(|_| flag1.load(Ordering::SeqCst)).unwrap();
+	}
+
+	#[test]
+	fn test_notify_none() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(50));
+			cvar2.set(true, Notify::None);
+		});
+
+		// nobody woke the waiter, so it only notices the new value after timing out
+		let res = cvar1.wait_for_ms(true, 200).unwrap();
+		assert!(res.result.is_satisfied());
+		assert_eq!(res.remaining, Duration::from_millis(0));
+	}
+
+	#[test]
+	fn test_get_or_wait() {
+		let cvar1 = Arc::new(ConditionVariable::new(1));
+		let cvar2 = cvar1.clone();
+
+		assert_eq!(cvar1.get_or_wait(|v| *v == 1).unwrap(), 1);
+
+		spawn(move || {
+			sleep(Duration::from_millis(50));
+			cvar2.set(2, Notify::All);
+		});
+
+		assert_eq!(cvar1.get_or_wait(|v| *v == 2).unwrap(), 2);
+	}
+
+	#[test]
+	fn test_try_wait_for() {
+		let cvar = ConditionVariable::new(1);
+
+		assert!(cvar.try_wait_for(|v| *v == 1).unwrap());
+		assert!(!cvar.try_wait_for(|v| *v == 2).unwrap());
+	}
+
+	#[test]
+	fn test_replace() {
+		struct Big(Vec<u8>);
+
+		let cvar = ConditionVariable::new(Big(vec![1]));
+		let previous = cvar.replace(Big(vec![2]), Notify::All).ok().unwrap();
+
+		assert_eq!(previous.0, vec![1]);
+		assert_eq!(cvar.with_lock(|v| v.0.clone()).unwrap(), vec![2]);
+	}
+
+	#[test]
+	fn test_take() {
+		let cvar1 = Arc::new(ConditionVariable::new(Vec::new()));
+		let cvar2 = cvar1.clone();
+
+		cvar1.update(|v| v.extend(&[1, 2, 3]), Notify::All);
+
+		let producer = spawn(move || cvar2.wait_for_condition(|v: &Vec<i32>| v.is_empty()).unwrap());
+
+		assert_eq!(cvar1.take(Notify::All), Some(vec![1, 2, 3]));
+		assert_eq!(producer.join().unwrap(), Vec::<i32>::new());
+	}
+
+	#[test]
+	fn test_timed_wait_for_timed_out() {
+		let cvar = ConditionVariable::new(false);
+
+		match cvar.timed_wait_for(true, Duration::from_millis(10)).unwrap().result {
+			WaitResult::Satisfied => panic!("nobody set the value"),
+			WaitResult::TimedOut => {},
+		}
+
+		#[allow(deprecated)]
+		let satisfied = cvar.wait_for_timeout(true, Duration::from_millis(10)).unwrap();
+		assert!(!satisfied);
+	}
+
+	#[test]
+	fn test_lock() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			let mut guard = cvar2.lock().unwrap();
+			*guard += 1;
+			*guard += 1;
+			cvar2.notify_all();
+		});
+
+		assert_eq!(cvar1.wait_for(2).unwrap(), 2);
+	}
+
+	#[test]
+	fn test_wait_for_condition_with_clock() {
+		use std::cell::Cell;
+
+		let cvar = ConditionVariable::new(false);
+		let ticks = Cell::new(0);
+		let clock = || { ticks.set(ticks.get() + 1); Duration::from_millis(ticks.get()) };
+
+		assert_eq!(cvar.wait_for_condition_with_clock(|v| *v, Duration::from_millis(10), clock).unwrap(), WaitResult::TimedOut);
+		assert!(ticks.get() >= 10);
+
+		cvar.set(true, Notify::All);
+		assert_eq!(cvar.wait_for_condition_with_clock(|v| *v, Duration::from_millis(10), clock).unwrap(), WaitResult::Satisfied);
+	}
+
+	#[test]
+	fn test_wait_for_any() {
+		let cvar1 = Arc::new(ConditionVariable::new("idle"));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set("busy", Notify::All);
+			cvar2.set("failed", Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_any(&["done", "failed"]).unwrap(), "failed");
+	}
+
+	#[test]
+	fn test_wait_for_generation_after() {
+		let cvar1 = Arc::new(ConditionVariable::new('A'));
+		let cvar2 = cvar1.clone();
+		let seen = cvar1.generation();
+
+		spawn(move || {
+			cvar2.set('B', Notify::All);
+			cvar2.set('A', Notify::All);
+		});
+
+		let (generation, _) = cvar1.wait_for_generation_after(seen).unwrap();
+		assert!(generation > seen);
+		assert_eq!(cvar1.wait_for_generation_after(seen + 1).unwrap(), (seen + 2, 'A'));
+	}
+
+	#[test]
+	fn test_fetch_update() {
+		let cvar = ConditionVariable::new(1);
+
+		assert_eq!(cvar.fetch_update(|v| Some(v * 10), Notify::All), Ok(1));
+		assert_eq!(cvar.fetch_update(|v| if *v < 10 { Some(0) } else { None }, Notify::All), Err(10));
+		assert_eq!(cvar.get().unwrap(), 10);
+	}
+
+	#[test]
+	fn test_wait_for_condition_ms_survives_spurious_wakeups() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+		let done = Arc::new(AtomicBool::new(false));
+		let done2 = done.clone();
+
+		let waker = spawn(move || {
+			while !done2.load(Ordering::SeqCst) {
+				cvar2.notify(Notify::All);
+				sleep(Duration::from_micros(100));
+			}
+		});
+
+		let before = Instant::now();
+		assert!(!cvar1.wait_for_condition_ms(|actual| *actual, 300).unwrap().result.is_satisfied());
+		let elapsed = before.elapsed();
+		done.store(true, Ordering::SeqCst);
+		waker.join().unwrap();
+
+		// a timeout that restarted or drifted on every wakeup would run far past this bound
+		assert!(elapsed >= Duration::from_millis(300));
+		assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+	}
+
+	#[test]
+	fn test_wait_for_condition_until() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let deadline = Instant::now() + Duration::from_millis(300);
+
+		spawn(move || {
+			sleep(Duration::from_millis(50));
+			cvar2.set(1, Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_condition_until(|v| *v == 1, deadline).unwrap().result, WaitResult::Satisfied);
+		assert_eq!(cvar1.wait_for_condition_until(|v| *v == 2, deadline).unwrap().result, WaitResult::TimedOut);
+		assert!(Instant::now() >= deadline);
+	}
+
+	#[test]
+	fn test_close() {
+		let consumer = Arc::new(ConditionVariable::new(0));
+		let producer = consumer.clone();
+
+		spawn(move || {
+			producer.set(1, Notify::All);
+			producer.close();
+		});
+
+		assert_eq!(consumer.wait_for(2), Err(WaitError::Closed));
+		assert_eq!(consumer.wait_for(1), Ok(1));
+		assert_eq!(consumer.timed_wait_for_condition(|v| *v == 2, Duration::from_secs(5)), Err(WaitError::Closed));
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_new_ignoring_poison() {
+		let cvar = Arc::new(ConditionVariable::new_ignoring_poison(1));
+		poison(&cvar);
+
+		assert_eq!(cvar.get().unwrap(), 1);
+		cvar.set(2, Notify::All);
+		assert_eq!(cvar.wait_for(2), Ok(2));
+		assert!(cvar.timed_wait_for(3, Duration::from_millis(10)).is_ok());
+	}
+
+	#[test]
+	fn test_with_clock() {
+		let time = Arc::new(Mutex::new(Instant::now()));
+		let cvar1 = Arc::new(ConditionVariable::with_clock(false, FakeClock(time.clone())));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.timed_wait_for_condition(|v| *v, Duration::from_secs(3600)));
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		let before = Instant::now();
+		*time.lock().unwrap() += Duration::from_secs(3601);
+		cvar1.notify(Notify::All);
+
+		assert_eq!(waiter.join().unwrap().map(|res| res.result), Ok(WaitResult::TimedOut));
+		assert!(before.elapsed() < Duration::from_secs(1));
+	}
+
+	#[test]
+	fn test_wait_for_either() {
+		use crate::{Either, wait_for_either};
+
+		let data = Arc::new(ConditionVariable::new(None));
+		let shutdown = Arc::new(ConditionVariable::new(false));
+		let shutdown2 = shutdown.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(50));
+			shutdown2.set(true, Notify::All);
+		});
+
+		let res = wait_for_either(&data, |d: &Option<u32>| d.is_some(), &shutdown, |s| *s).unwrap();
+		assert_eq!(res, Either::Right(true));
+
+		data.set(Some(7), Notify::All);
+		assert_eq!(wait_for_either(&data, |d| d.is_some(), &shutdown, |s| *s).unwrap(), Either::Left(Some(7)));
+	}
+
+	#[test]
+	fn test_get_copy() {
+		let cvar = ConditionVariable::new(3u32);
+		cvar.update(|v| *v += 1, Notify::None);
+
+		assert_eq!(cvar.get_copy().unwrap(), 4);
+	}
+
+	#[test]
+	fn test_wait_for_condition_mut() {
+		let cvar1 = Arc::new(ConditionVariable::new(Vec::new()));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.update(|queue| queue.push(1), Notify::All);
+		});
+
+		let mut item = None;
+		cvar1.wait_for_condition_mut(|queue| { item = queue.pop(); item.is_some() }).unwrap();
+		assert_eq!(item, Some(1));
+		assert!(cvar1.get().unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_wait_for_condition_ms_zero_and_negative() {
+		let cvar = ConditionVariable::new(false);
+		let calls = std::cell::Cell::new(0);
+		let count = |v: &bool| { calls.set(calls.get() + 1); *v };
+
+		for &timeout_ms in &[0, -1, i64::MIN] {
+			calls.set(0);
+			let before = Instant::now();
+			let res = cvar.wait_for_condition_ms(count, timeout_ms).unwrap();
+
+			assert_eq!(res.result, WaitResult::TimedOut);
+			assert_eq!(res.remaining, Duration::from_millis(0));
+			assert!(before.elapsed() < Duration::from_millis(50));
+			assert_eq!(calls.get(), 1);
+		}
+
+		cvar.set(true, Notify::None);
+		assert!(cvar.wait_for_condition_ms(|v| *v, 0).unwrap().result.is_satisfied());
+		assert!(cvar.wait_for_condition_ms(|v| *v, -1).unwrap().result.is_satisfied());
+	}
+
+	#[test]
+	fn test_timeout_beyond_u32_max_ms() {
+		let time = Arc::new(Mutex::new(Instant::now()));
+		let cvar1 = Arc::new(ConditionVariable::with_clock(false, FakeClock(time.clone())));
+		let cvar2 = cvar1.clone();
+
+		let timeout_ms = u32::MAX as i64 * 2;
+		let waiter = spawn(move || cvar2.wait_for_condition_ms(|v| *v, timeout_ms));
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		*time.lock().unwrap() += Duration::from_millis(u32::MAX as u64 + 1);
+		cvar1.notify(Notify::All);
+		sleep(Duration::from_millis(50));
+		assert_eq!(cvar1.waiter_count(), 1);
+
+		*time.lock().unwrap() += Duration::from_millis(u32::MAX as u64);
+		cvar1.notify(Notify::All);
+		assert_eq!(waiter.join().unwrap().unwrap().result, WaitResult::TimedOut);
+	}
+
+	#[test]
+	#[cfg(feature = "tracing")]
+	fn test_trace_values() {
+		let cvar = ConditionVariable::builder().value(0).trace_values().build();
+
+		cvar.set(1, Notify::All);
+		assert_eq!(cvar.wait_for(1), Ok(1));
+		assert!(!cvar.timed_wait_for(2, Duration::from_millis(1)).unwrap().result.is_satisfied());
+	}
+
+	#[test]
+	fn test_wait_for_n_updates() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let start = cvar1.generation();
+
+		let consumer = spawn(move || cvar2.wait_for_n_updates(5).unwrap());
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		for i in 0..10 {
+			cvar1.set(i % 2, Notify::All);
+		}
+
+		assert!(consumer.join().unwrap() >= start + 5);
+	}
+
+	#[test]
+	fn test_wait_ms_ignores_spurious_wakeups() {
+		let cvar1 = Arc::new(ConditionVariable::new(()));
+		let cvar2 = cvar1.clone();
+
+		let waker = spawn(move || {
+			while cvar2.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			// wakes the condvar behind the API's back, just like a spurious wakeup would
+			let (lock, cvar) = &cvar2.pair;
+			let _guard = lock.lock().unwrap();
+			cvar.notify_all();
+		});
+
+		let before = Instant::now();
+		assert!(!cvar1.wait_until_ms(100));
+		assert!(before.elapsed() >= Duration::from_millis(100));
+		waker.join().unwrap();
+
+		let cvar2 = cvar1.clone();
+		let waker = spawn(move || {
+			while cvar2.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			let (lock, cvar) = &cvar2.pair;
+			let _guard = lock.lock().unwrap();
+			cvar.notify_all();
+		});
+
+		let before = Instant::now();
+		let (guard, notified) = cvar1.wait_ms(100).unwrap();
+		assert!(!notified);
+		assert!(before.elapsed() >= Duration::from_millis(100));
+		drop(guard);
+		waker.join().unwrap();
+
+		let cvar2 = cvar1.clone();
+		spawn(move || {
+			while cvar2.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			cvar2.notify(Notify::All);
+		});
+		assert!(cvar1.wait_until_ms(5000));
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_get_or() {
+		let cvar = Arc::new(ConditionVariable::new(1));
+		assert_eq!(cvar.get_or(0), 1);
+
+		poison(&cvar);
+		assert_eq!(cvar.get_or(0), 0);
+	}
+
+	#[test]
+	fn test_wait_for_condition_spin() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		assert!(ConditionVariable::new(true).wait_for_condition_spin(|v| *v, 0).unwrap());
+
+		spawn(move || {
+			sleep(Duration::from_millis(10));
+			cvar2.set(true, Notify::All);
+		});
+		assert!(cvar1.wait_for_condition_spin(|v| *v, 100).unwrap());
+	}
+
+	#[test]
+	fn test_try_set() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		assert!(!cvar1.try_set(1, 1, Notify::All));
+
+		let consumer = spawn(move || cvar2.wait_for(2).unwrap());
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		assert!(!cvar1.try_set(1, 2, Notify::All));
+		assert!(cvar1.try_set(2, 1, Notify::All));
+		assert_eq!(consumer.join().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_map_into() {
+		let cvar = ConditionVariable::builder().value(42u32).default_notify(Notify::One).build();
+
+		let cvar = cvar.map_into(|v| v.to_string());
+		assert_eq!(cvar.get().unwrap(), "42");
+		assert_eq!(cvar.default_notify, Notify::One);
+	}
+
+	#[test]
+	fn test_wait_for_each() {
+		let cvar1 = Arc::new(ConditionVariable::new("init"));
+		let cvar2 = cvar1.clone();
+
+		let consumer = spawn(move || cvar2.wait_for_each(&["connecting", "ready", "closed"]));
+
+		for state in &["connecting", "ready", "closed"] {
+			while cvar1.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			cvar1.set(state, Notify::All);
+			// give the consumer time to see the state before it is replaced
+			sleep(Duration::from_millis(20));
+		}
+
+		assert_eq!(consumer.join().unwrap(), Ok(()));
+	}
+
+	#[test]
+	fn test_wait_timeout_unit() {
+		let cvar1 = Arc::new(ConditionVariable::new(()));
+		let cvar2 = cvar1.clone();
+
+		assert_eq!(cvar1.wait_timeout(Duration::from_millis(10)), WaitResult::TimedOut);
+
+		spawn(move || {
+			while cvar2.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			cvar2.notify(Notify::One);
+		});
+		assert_eq!(cvar1.wait_timeout(Duration::from_secs(5)), WaitResult::Satisfied);
+	}
+
+	#[test]
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_is_poisoned() {
+		let cvar = Arc::new(ConditionVariable::new(0));
+		assert!(!cvar.is_poisoned());
+
+		poison(&cvar);
+		assert!(cvar.is_poisoned());
+	}
+
+	#[test]
+	fn test_wait_for_condition_ms_locked() {
+		let cvar = ConditionVariable::new(1);
+
+		assert!(cvar.wait_for_condition_ms_locked(|v| *v == 2, 10).unwrap().is_none());
+
+		let mut guard = cvar.wait_for_condition_ms_locked(|v| *v == 1, 10).unwrap().unwrap();
+		*guard += 1;
+		drop(guard);
+		assert_eq!(cvar.get().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_notify_on_change_only() {
+		let cvar = ConditionVariable::builder().value(1).notify_on_change_only().build();
+		let start = cvar.generation();
+
+		cvar.set(1, Notify::All);
+		assert_eq!(cvar.generation(), start);
+
+		cvar.set(2, Notify::All);
+		assert_eq!(cvar.generation(), start + 1);
+	}
+
+	#[test]
+	fn test_stateful_predicate() {
+		let cvar = ConditionVariable::new(0);
+		let mut calls = 0;
+
+		cvar.wait_for_condition(|_| { calls += 1; calls == 1 }).unwrap();
+		assert_eq!(calls, 1);
+
+		let res = cvar.timed_wait_for_condition(|_| { calls += 1; false }, Duration::from_millis(0)).unwrap();
+		assert_eq!(res.result, WaitResult::TimedOut);
+		assert_eq!(calls, 2);
+	}
+
+	#[test]
+	fn test_update_reporting() {
+		let cvar = ConditionVariable::new(vec![1]);
+
+		assert_eq!(cvar.update_reporting(|v| v.push(2), Notify::All), Some((vec![1], vec![1, 2])));
+		assert_eq!(cvar.get().unwrap(), vec![1, 2]);
+	}
+
+	#[test]
+	fn test_wait_for_or_timeout() {
+		use crate::WaitOutcome;
+
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		assert_eq!(cvar1.wait_for_or_timeout(1, Duration::from_millis(10)), Ok(WaitOutcome::TimedOut));
+
+		spawn(move || {
+			sleep(Duration::from_millis(10));
+			cvar2.set(1, Notify::All);
+		});
+		assert_eq!(cvar1.wait_for_or_timeout(1, Duration::from_secs(5)), Ok(WaitOutcome::Ready(1)));
+	}
+
+	#[test]
+	fn test_wait_for_condition_cancellable() {
+		let cvar = Arc::new(ConditionVariable::new(0));
+		let cancel = Arc::new(AtomicBool::new(false));
+		let cancel2 = cancel.clone();
+
+		assert!(ConditionVariable::new(1).wait_for_condition_cancellable(|v| *v == 1, &cancel).unwrap());
+
+		spawn(move || {
+			sleep(Duration::from_millis(20));
+			cancel2.store(true, Ordering::SeqCst);
+		});
+		let before = Instant::now();
+		assert!(!cvar.wait_for_condition_cancellable(|v| *v == 1, &cancel).unwrap());
+		assert!(before.elapsed() < Duration::from_secs(1));
+	}
+
+	#[test]
+	fn test_set_all() {
+		let workers: Vec<_> = (0..3).map(|_| ConditionVariable::new(false)).collect();
+
+		crate::set_all(&workers.iter().collect::<Vec<_>>(), true, Notify::All);
+		assert!(workers.iter().all(|w| w.get().unwrap()));
+	}
+
+	#[test]
+	fn test_generation_wraps() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		cvar1.generation.store(u64::MAX, Ordering::SeqCst);
+
+		let waiter = spawn(move || cvar2.wait_for_generation_after(u64::MAX).unwrap());
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		cvar1.set(1, Notify::All);
+		assert_eq!(waiter.join().unwrap(), (0, 1));
+		assert!(crate::newer_generation(1, u64::MAX - 1));
+		assert!(!crate::newer_generation(u64::MAX, 1));
+	}
+
+	#[test]
+	fn test_peek() {
+		struct NotClone(u32);
+
+		let cvar = ConditionVariable::new(NotClone(2));
+		assert_eq!(cvar.peek(|v| v.0 * 2).unwrap(), 4);
+	}
+
+	#[test]
+	fn test_wait_for_condition_stats() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			for _ in 0..3 {
+				while cvar2.waiter_count() == 0 {
+					sleep(Duration::from_millis(1));
+				}
+				cvar2.notify(Notify::All);
+				sleep(Duration::from_millis(5));
+			}
+			cvar2.set(true, Notify::All);
+		});
+
+		let stats = cvar1.wait_for_condition_stats(|v| *v).unwrap();
+		assert!(stats.iterations > 1);
+		assert!(stats.elapsed >= Duration::from_millis(10));
+	}
 
-impl<T:PartialEq+Clone> ConditionVariable<T> {
-	pub fn new(value: T) -> ConditionVariable<T> {
-		ConditionVariable {
-			pair: (Mutex::new(value), Condvar::new())
+	#[test]
+	fn test_from_fn() {
+		let cvar = ConditionVariable::from_fn(|| Vec::<u8>::with_capacity(1024));
+
+		cvar.update(|buf| buf.extend_from_slice(&[0; 1024]), Notify::All);
+		assert!(cvar.peek(|buf| buf.len() == 1024 && buf.capacity() == 1024).unwrap());
+	}
+
+	#[test]
+	fn test_wait_for_condition_ref() {
+		let cvar = ConditionVariable::new(4);
+		let even = |v: &i32| v % 2 == 0;
+		let positive = |v: &i32| *v > 0;
+		let conditions: [&dyn Fn(&i32) -> bool; 2] = [&even, &positive];
+
+		for _ in 0..2 {
+			for cond in &conditions {
+				assert_eq!(cvar.wait_for_condition_ref(*cond), Ok(4));
+			}
 		}
 	}
 
-	pub fn set(&self, value: T, notify: Notify) {
-		let &(ref lock, ref cvar) = &self.pair;
+	#[test]
+	fn test_wait_for_condition_bounded() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
 
-		let mut data = lock.lock().unwrap();
-		*data = value;
+		let notifier = spawn(move || {
+			while Arc::strong_count(&cvar2) > 1 {
+				cvar2.notify(Notify::All);
+				sleep(Duration::from_millis(1));
+			}
+		});
 
-		match notify {
-			Notify::One => cvar.notify_one(),
-			Notify::All => cvar.notify_all(),
-		}
+		assert_eq!(cvar1.wait_for_condition_bounded(|v| *v < 0, 3), Ok(false));
+		assert_eq!(cvar1.wait_for_condition_bounded(|v| *v == 0, 0), Ok(true));
+		drop(cvar1);
+		notifier.join().unwrap();
 	}
 
-	pub fn get(&self) -> Result<T, PoisonError<MutexGuard<T>>> {
-		let &(ref lock, _) = &self.pair;
+	#[test]
+	fn test_wait_timeout_while() {
+		let cvar1 = Arc::new(ConditionVariable::new(true));
+		let cvar2 = cvar1.clone();
+
+		let (pending, timed_out) = cvar1.wait_timeout_while(Duration::from_millis(10), |pending| *pending).unwrap();
+		assert!(*pending);
+		assert!(timed_out);
+		drop(pending);
 
-		let data = try!(lock.lock());
+		spawn(move || {
+			cvar2.set(false, Notify::All);
+		});
 
-		Ok(data.clone())
+		let (pending, timed_out) = cvar1.wait_timeout_while(Duration::from_secs(10), |pending| *pending).unwrap();
+		assert!(!*pending);
+		assert!(!timed_out);
 	}
 
-	pub fn wait_for(&self, expected: T) -> Result<(), PoisonError<MutexGuard<T>>> {
-		self.wait_for_in(&[expected])
+	#[derive(Debug, Clone, PartialEq)]
+	enum Rpc {
+		Idle,
+		Request(i32),
+		Reply(i32),
 	}
 
-	pub fn wait_for_in(&self, expected: &[T]) -> Result<(), PoisonError<MutexGuard<T>>> {
-		self.wait_for_condition(|actual| expected.contains(actual))
+	#[test]
+	fn test_set_and_wait_for() {
+		let cvar1 = Arc::new(ConditionVariable::new(Rpc::Idle));
+		let cvar2 = cvar1.clone();
+
+		let responder = spawn(move || {
+			let request = cvar2.wait_for_condition(|rpc| matches!(rpc, Rpc::Request(_))).unwrap();
+			if let Rpc::Request(n) = request {
+				cvar2.set(Rpc::Reply(n * 2), Notify::All);
+			}
+		});
+
+		let reply = cvar1.set_and_wait_for(Rpc::Request(21), |rpc| matches!(rpc, Rpc::Reply(_)));
+		assert_eq!(reply, Ok(Rpc::Reply(42)));
+		responder.join().unwrap();
 	}
 
-	pub fn wait_for_condition<F:Fn(&T) -> bool>(&self, cond_func: F) -> Result<(), PoisonError<MutexGuard<T>>> {
-		let &(ref lock, ref cvar) = &self.pair;
-		let mut actual = try!(lock.lock());
-		
-		while !cond_func(&*actual) {
-			actual = try!(cvar.wait(actual));
+	#[test]
+	fn test_attach_cancel() {
+		let cvar1 = Arc::new(ConditionVariable::new(false));
+		let cvar2 = cvar1.clone();
+		let cancel = Arc::new(AtomicBool::new(false));
+		cvar1.attach_cancel(cancel.clone());
+
+		let waiter = spawn(move || cvar2.wait_for(true));
+
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
 		}
+		cancel.store(true, Ordering::SeqCst);
 
-		Ok(())
+		assert_eq!(waiter.join().unwrap(), Err(WaitError::Cancelled));
+		assert_eq!(cvar1.timed_wait_for(true, Duration::from_secs(10)), Err(WaitError::Cancelled));
 	}
 
-	pub fn wait_for_ms(&self, expected: T, timeout_ms: i64) -> Result<bool, PoisonError<(MutexGuard<T>,bool)>> {
-		self.wait_for_in_ms(&[expected], timeout_ms)
+	#[test]
+	fn test_wait_for_condition_polling() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			sleep(Duration::from_millis(20));
+			cvar2.set(1, Notify::None);
+		});
+
+		assert_eq!(cvar1.wait_for_condition_polling(|v| *v == 1, Duration::from_millis(5)), Ok(1));
 	}
 
-	pub fn wait_for_in_ms(&self, expected: &[T], timeout_ms: i64)
-		-> Result<bool, PoisonError<(MutexGuard<T>,bool)>>
-	{
-		self.wait_for_condition_ms(|actual| expected.contains(actual), timeout_ms)
+	#[test]
+	fn test_wait_for_condition_within() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let mut budget = Budget::new(Duration::from_millis(100));
+
+		spawn(move || {
+			sleep(Duration::from_millis(20));
+			cvar2.set(1, Notify::All);
+		});
+
+		assert_eq!(cvar1.wait_for_condition_within(|v| *v == 1, &mut budget).map(|res| res.result), Ok(WaitResult::Satisfied));
+		assert!(budget.remaining() < Duration::from_millis(100));
+
+		assert_eq!(cvar1.wait_for_condition_within(|v| *v == 2, &mut budget).map(|res| res.result), Ok(WaitResult::TimedOut));
+		assert!(budget.is_exhausted());
+		assert_eq!(cvar1.wait_for_condition_within(|v| *v == 1, &mut budget).map(|res| res.result), Ok(WaitResult::TimedOut));
 	}
 
-	pub fn wait_for_condition_ms<F:Fn(&T) -> bool>(&self, cond_func: F, timeout_ms: i64)
-		-> Result<bool, PoisonError<(MutexGuard<T>,bool)>>
-	{
-		let &(ref lock, ref cvar) = &self.pair;
-		let mut actual = lock.lock().unwrap();
+	#[test]
+	#[cfg(debug_assertions)]
+	#[should_panic(expected = "unexpected ConditionVariable value")]
+	fn test_debug_assert_value() {
+		let cvar = ConditionVariable::new(1);
+		cvar.debug_assert_value(&1);
 
-		let mut remaining_ms = timeout_ms;
-		while !cond_func(&*actual) && remaining_ms > 0 {
-			let before = Instant::now();
+		cvar.set(2, Notify::All);
+		cvar.debug_assert_value(&1);
+	}
 
-			let (new, _) = try!(cvar.wait_timeout_ms(actual, remaining_ms as u32));
-			actual = new;
+	#[test]
+	fn test_wait_for_in_set() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let expected: HashSet<_> = (100..112).collect();
 
-			let elapsed = before.elapsed();
-			remaining_ms -= (elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1000_000) as u64) as i64;
-		}
+		spawn(move || {
+			for i in [5, 50, 105] {
+				cvar2.set(i, Notify::All);
+			}
+		});
 
-		Ok(cond_func(&*actual))
+		assert_eq!(cvar1.wait_for_in_set(&expected), Ok(105));
+		assert_eq!(cvar1.wait_for_in_iter(100..112), Ok(105));
 	}
-}
 
-impl ConditionVariable<()> {
-	/// waits for a notify (useful if T==())
-	pub fn wait_ms(&self, timeout_ms: u32) -> LockResult<(MutexGuard<()>,bool)>
-	{
-		let &(ref lock, ref cvar) = &self.pair;
-		let guard = lock.lock().unwrap();
-		
-		cvar.wait_timeout_ms(guard, timeout_ms)
+	#[test]
+	fn test_poll_condition() {
+		let cvar = ConditionVariable::new(0);
+
+		assert_eq!(cvar.poll_condition(|v| *v == 1), Poll::Pending);
+		cvar.set(1, Notify::None);
+		assert_eq!(cvar.poll_condition(|v| *v == 1), Poll::Ready(1));
 	}
-}
 
-#[cfg(test)]
-mod tests {
-	use std::sync::Arc;
-	use std::thread::{sleep, spawn};
-	use std::time::Duration;
+	#[test]
+	fn test_wait_for_try_condition() {
+		let cvar1 = Arc::new(ConditionVariable::new("1"));
+		let cvar2 = cvar1.clone();
+		let parse = |v: &&str| v.parse::<i32>().map(|n| n > 1);
+
+		spawn(move || {
+			cvar2.set("2", Notify::All);
+		});
+		assert_eq!(cvar1.wait_for_try_condition(parse), Ok(Ok(())));
 
-	use ::Notify;
-	use ::ConditionVariable;
+		cvar1.set("two", Notify::All);
+		assert!(cvar1.wait_for_try_condition(parse).unwrap().is_err());
+	}
 
 	#[test]
-	fn test_wait_for() {
+	#[cfg(not(feature = "parking_lot"))]
+	fn test_clear_poison() {
+		let cvar = Arc::new(ConditionVariable::new(1));
+		poison(&cvar);
+		assert!(cvar.get().is_err());
+
+		cvar.clear_poison();
+		assert!(!cvar.is_poisoned());
+		assert_eq!(cvar.get().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_wait_for_condition_with_heartbeat() {
 		let cvar1 = Arc::new(ConditionVariable::new(false));
 		let cvar2 = cvar1.clone();
 
 		spawn(move || {
+			sleep(Duration::from_millis(300));
 			cvar2.set(true, Notify::All);
 		});
 
-		cvar1.wait_for(true).unwrap();
+		let mut ticks = Vec::new();
+		cvar1.wait_for_condition_with_heartbeat(|v| *v, Duration::from_millis(100), |elapsed| ticks.push(elapsed)).unwrap();
+		assert!(!ticks.is_empty());
+		assert!(ticks[0] >= Duration::from_millis(100));
 	}
 
 	#[test]
-	fn test_wait_for_ms() {
-		let cvar1 = Arc::new(ConditionVariable::new(false));
+	fn test_wait_for_condition_with_heartbeat_zero_interval() {
+		let cvar = ConditionVariable::new(false);
+		let ticks = std::cell::Cell::new(0);
+		let mut waited = Duration::new(0, 0);
+
+		cvar.wait_for_condition_with_heartbeat(|_| ticks.get() == 3, Duration::new(0, 0), |elapsed| {
+			ticks.set(ticks.get() + 1);
+			waited = elapsed;
+		}).unwrap();
+		assert_eq!(ticks.get(), 3);
+		assert!(waited >= Duration::from_millis(3));
+	}
+
+	#[test]
+	fn test_signal() {
+		let cvar1 = Arc::new(ConditionVariable::new(()));
 		let cvar2 = cvar1.clone();
 
 		spawn(move || {
-			sleep(Duration::from_millis(500));
-			cvar2.set(true, Notify::All);
+			while cvar2.waiter_count() == 0 {
+				sleep(Duration::from_millis(1));
+			}
+			cvar2.signal(Notify::All);
 		});
 
-		assert_eq!(cvar1.wait_for_ms(true, 1000).unwrap(), true);
+		let (_guard, notified) = cvar1.wait_ms(10_000).unwrap();
+		assert!(notified);
 	}
 
 	#[test]
-	fn test_wait_for_ms_fail() {
+	fn test_wait_for_condition_unlocked() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || {
+			cvar2.wait_for_condition_unlocked(|v| {
+				sleep(Duration::from_millis(20));
+				*v >= 3
+			})
+		});
+
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+		for i in 1..4 {
+			let start = Instant::now();
+			cvar1.set(i, Notify::All);
+			assert!(start.elapsed() < Duration::from_millis(20));
+		}
+
+		assert_eq!(waiter.join().unwrap(), Ok(3));
+	}
+
+	#[test]
+	fn test_id() {
+		let cvar1 = ConditionVariable::new(0);
+		let cvar2 = ConditionVariable::new(0);
+
+		assert_ne!(cvar1.id(), cvar2.id());
+		cvar1.set(1, Notify::All);
+		assert_eq!(cvar1.id(), cvar1.id());
+	}
+
+	#[test]
+	fn test_borrowing_predicates() {
+		// predicates are never boxed or stored, so they may borrow from the caller's stack
+		let cvar = ConditionVariable::new(5);
+		let threshold = 3;
+		let at_least = |v: &i32| *v >= threshold;
+
+		assert_eq!(cvar.wait_for_condition(at_least), Ok(5));
+		assert_eq!(*cvar.wait_for_condition_locked(at_least).unwrap(), 5);
+		assert_eq!(cvar.wait_for_condition_ref(&at_least), Ok(5));
+		assert_eq!(cvar.wait_for_condition_unlocked(at_least), Ok(5));
+		assert_eq!(cvar.wait_for_condition_bounded(at_least, 0), Ok(true));
+		assert!(cvar.timed_wait_for_condition(at_least, Duration::from_millis(10)).unwrap().result.is_satisfied());
+		assert!(cvar.wait_for_condition_ms(at_least, 10).unwrap().result.is_satisfied());
+		assert!(cvar.wait_for_condition_within(at_least, &mut Budget::new(Duration::from_millis(10))).unwrap()
+			.result.is_satisfied());
+		assert_eq!(cvar.poll_condition(at_least), Poll::Ready(5));
+	}
+
+	#[test]
+	fn test_reset() {
+		let cvar = ConditionVariable::new_resettable(0);
+		cvar.set(1, Notify::All);
+
+		assert!(cvar.reset(Notify::All));
+		assert_eq!(cvar.get().unwrap(), 0);
+
+		let cvar = ConditionVariable::new(0);
+		cvar.set(1, Notify::All);
+		assert!(!cvar.reset(Notify::All));
+		assert_eq!(cvar.get().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_wait_for_value_locked() {
+		let cvar1 = Arc::new(ConditionVariable::new(VecDeque::new()));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.update(|queue| queue.extend([1, 2]), Notify::All);
+		});
+
+		let (mut queue, snapshot) = cvar1.wait_for_value_locked(|queue| !queue.is_empty()).unwrap();
+		assert_eq!(queue.pop_front(), Some(1));
+		drop(queue);
+
+		assert_eq!(snapshot, [1, 2]);
+		assert_eq!(cvar1.get().unwrap(), [2]);
+	}
+
+	#[derive(Debug, Clone, PartialEq)]
+	struct Versioned {
+		version: u64,
+		payload: Vec<u8>,
+	}
+
+	#[test]
+	fn test_wait_for_version() {
+		let cvar1 = Arc::new(ConditionVariable::new(Versioned { version: 0, payload: Vec::new() }));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			for version in 1..4 {
+				cvar2.update(|v| {
+					v.version = version;
+					v.payload = vec![version as u8; 1024];
+				}, Notify::All);
+			}
+		});
+
+		let value = cvar1.wait_for_version(|v| v.version, 3).unwrap();
+		assert_eq!(value.version, 3);
+		assert_eq!(value.payload, vec![3; 1024]);
+	}
+
+	#[test]
+	fn test_try_set_for() {
+		// a stopped clock mustn't stretch the lock timeout
+		let cvar1 = Arc::new(ConditionVariable::with_clock(0, FakeClock(Arc::new(Mutex::new(Instant::now())))));
+		let cvar2 = cvar1.clone();
+		let locked = Arc::new(AtomicBool::new(false));
+		let locked2 = locked.clone();
+
+		let holder = spawn(move || {
+			let _data = cvar2.lock().unwrap();
+			locked2.store(true, Ordering::SeqCst);
+			sleep(Duration::from_millis(100));
+		});
+		while !locked.load(Ordering::SeqCst) {
+			sleep(Duration::from_millis(1));
+		}
+
+		assert!(!cvar1.try_set_for(1, Notify::All, Duration::from_millis(10)));
+		holder.join().unwrap();
+		assert!(cvar1.try_set_for(2, Notify::All, Duration::from_millis(10)));
+		assert_eq!(cvar1.get().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_wait_for_condition_detect_livelock() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let producer = spawn(move || {
+			while Arc::strong_count(&cvar2) > 1 {
+				cvar2.set(0, Notify::All);
+				sleep(Duration::from_millis(1));
+			}
+		});
+
+		assert_eq!(cvar1.wait_for_condition_detect_livelock(|v| *v == 1, 5), Err(WaitError::SuspectedLivelock));
+		drop(cvar1);
+		producer.join().unwrap();
+	}
+
+	static READY: ConditionVariable<bool> = ConditionVariable::new(false);
+
+	#[test]
+	fn test_const_new() {
+		spawn(|| {
+			READY.set(true, Notify::All);
+		});
+
+		assert_eq!(READY.wait_for(true), Ok(true));
+	}
+
+	#[test]
+	fn test_wait_for_stable() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let start = Instant::now();
+		spawn(move || {
+			for i in 1..4 {
+				sleep(Duration::from_millis(20));
+				cvar2.set(i, Notify::All);
+			}
+		});
+
+		assert_eq!(cvar1.wait_for_stable(Duration::from_millis(100)), Ok(3));
+		assert!(start.elapsed() >= Duration::from_millis(160));
+	}
+
+	#[test]
+	fn test_wait_for_condition_full() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let cancel = Arc::new(AtomicBool::new(false));
+		let timeout = Some(Duration::from_millis(20));
+
+		spawn(move || {
+			cvar2.set(1, Notify::All);
+		});
+		assert_eq!(cvar1.wait_for_condition_full(|v| *v == 1, None, Some(&cancel)), Ok(WaitOutcome::Ready(1)));
+
+		assert_eq!(cvar1.wait_for_condition_full(|v| *v == 2, timeout, Some(&cancel)), Ok(WaitOutcome::TimedOut));
+		assert_eq!(cvar1.wait_for_condition_full(|v| *v == 2, timeout, None), Ok(WaitOutcome::TimedOut));
+
+		let cancel2 = cancel.clone();
+		spawn(move || {
+			sleep(Duration::from_millis(20));
+			cancel2.store(true, Ordering::SeqCst);
+		});
+		assert_eq!(cvar1.wait_for_condition_full(|v| *v == 2, None, Some(&cancel)), Ok(WaitOutcome::Cancelled));
+	}
+
+	#[derive(Debug, Clone)]
+	enum State {
+		Starting,
+		Error(String),
+	}
+
+	#[test]
+	fn test_wait_for_matching() {
+		let cvar1 = Arc::new(ConditionVariable::new(State::Starting));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set(State::Error("disk full".to_string()), Notify::All);
+		});
+
+		match cvar1.wait_for_matching(|state| matches!(state, State::Error(_))) {
+			Ok(State::Error(err)) => assert_eq!(err, "disk full"),
+			res => panic!("unexpected {:?}", res),
+		}
+	}
+
+	#[test]
+	fn test_set_one_safe() {
+		const WAITERS: i32 = 6;
+
+		for _ in 0..20 {
+			let cvar = Arc::new(ConditionVariable::new(-1));
+
+			let mut waiters: Vec<_> = (0..WAITERS).map(|i| {
+				let cvar = cvar.clone();
+				spawn(move || match i % 2 {
+					0 => cvar.wait_for(i).is_ok(),
+					_ => cvar.wait_for_ms(i, 5000).unwrap().result.is_satisfied(),
+				})
+			}).collect();
+			while cvar.waiter_count() < WAITERS as usize {
+				sleep(Duration::from_millis(1));
+			}
+
+			for i in (0..WAITERS).rev() {
+				cvar.set_one_safe(i);
+				assert!(waiters.pop().unwrap().join().unwrap());
+			}
+		}
+	}
+
+	#[test]
+	fn test_wait_for_condition_limited() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let notifier = spawn(move || {
+			while Arc::strong_count(&cvar2) > 1 {
+				cvar2.notify(Notify::All);
+				sleep(Duration::from_millis(1));
+			}
+		});
+
+		let mut evals = 0;
+		assert_eq!(cvar1.wait_for_condition_limited(|_| { evals += 1; false }, 4), Ok(false));
+		assert_eq!(evals, 4);
+		assert_eq!(cvar1.wait_for_condition_limited(|_| { evals += 1; true }, 0), Ok(false));
+		assert_eq!(evals, 4);
+
+		drop(cvar1);
+		notifier.join().unwrap();
+	}
+
+	#[test]
+	fn test_stats() {
+		let cvar = ConditionVariable::new(0);
+
+		cvar.set(1, Notify::One);
+		cvar.set(2, Notify::Many(2));
+		cvar.set(3, Notify::All);
+		cvar.set(4, Notify::None);
+		cvar.wait_for(4).unwrap();
+		assert!(!cvar.timed_wait_for(5, Duration::from_millis(1)).unwrap().result.is_satisfied());
+
+		// nobody was waiting, so the `Notify::Many` issued no wakeups
+		assert_eq!(cvar.stats(), CvStats {
+			notify_one_count: 1,
+			notify_all_count: 1,
+			wait_count: 2,
+			timeout_count: 1,
+		});
+	}
+
+	#[test]
+	fn test_timed_wait_elapsed() {
 		let cvar1 = Arc::new(ConditionVariable::new(false));
 		let cvar2 = cvar1.clone();
 
 		spawn(move || {
-			sleep(Duration::from_millis(1000));
+			sleep(Duration::from_millis(200));
 			cvar2.set(true, Notify::All);
 		});
 
-		assert_eq!(cvar1.wait_for_ms(true, 500).unwrap(), false);
+		let res = cvar1.wait_for_ms(true, 2000).unwrap();
+		assert!(res.result.is_satisfied());
+		assert!(res.elapsed >= Duration::from_millis(150));
+		assert!(res.elapsed < Duration::from_millis(1000));
+		assert!(res.elapsed + res.remaining <= Duration::from_millis(2000));
+
+		let res = cvar1.wait_for_ms(true, 2000).unwrap();
+		assert!(res.elapsed < Duration::from_millis(100));
+
+		let res = cvar1.timed_wait_for(false, Duration::from_millis(200)).unwrap();
+		assert_eq!(res.result, WaitResult::TimedOut);
+		assert!(res.elapsed >= Duration::from_millis(200));
+
+		let mut budget = Budget::new(Duration::from_millis(2000));
+		let res = cvar1.wait_for_condition_within(|v| *v, &mut budget).unwrap();
+		assert!(res.elapsed < Duration::from_millis(100));
+		assert_eq!(res.remaining, budget.remaining());
+	}
+
+	#[test]
+	fn test_freeze() {
+		let cvar = ConditionVariable::new_resettable(0);
+		assert!(cvar.set(1, Notify::All));
+
+		cvar.freeze();
+		assert!(cvar.is_frozen());
+		assert!(!cvar.set(2, Notify::All));
+		assert!(!cvar.update(|value| *value += 1, Notify::All));
+		assert!(!cvar.set_if_changed(3, Notify::All));
+		assert!(!cvar.try_set_for(4, Notify::All, Duration::from_millis(10)));
+		assert_eq!(cvar.fetch_update(|value| Some(value + 1), Notify::All), Err(1));
+		assert_eq!(cvar.set_and_wait_for(5, |_| true), Err(WaitError::Frozen));
+		assert!(!cvar.reset(Notify::All));
+		assert!(!cvar.batch_set(vec![6, 7], Notify::All));
+		assert_eq!(cvar.replace(8, Notify::All), Err(8));
+		assert_eq!(cvar.set_and_get(8, Notify::All), Err(8));
+		assert_eq!(cvar.take(Notify::All), None);
+		assert_eq!(cvar.modify(|v| { *v = 9; (true, ()) }), None);
+		assert_eq!(cvar.update_reporting(|v| *v = 9, Notify::All), None);
+		assert!(!cvar.set_one_safe(9));
+		assert!(cvar.lock_and_notify(Notify::All).is_none());
+		assert!(!cvar.set_default(9));
+		assert!(!cvar.compare_and_set(1, 9, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 1);
+
+		let arc = ConditionVariable::new(Arc::new(1));
+		arc.freeze();
+		assert!(!arc.set_value(2, Notify::All));
+		assert_eq!(*arc.get_arc(), 1);
+		assert_eq!(cvar.wait_for(1).unwrap(), 1);
+
+		cvar.thaw();
+		assert!(cvar.set(2, Notify::All));
+		assert_eq!(cvar.get().unwrap(), 2);
+	}
+
+	#[test]
+	fn test_stats_count_polling_waits_once() {
+		use crate::{Either, wait_for_either};
+
+		let cvar = ConditionVariable::new(0);
+		let res = cvar.wait_for_condition_full(|v| *v == 1, Some(Duration::from_millis(200)), Some(&AtomicBool::new(false)));
+		assert_eq!(res, Ok(WaitOutcome::TimedOut));
+		assert_eq!(cvar.stats().wait_count, 1);
+		assert_eq!(cvar.stats().timeout_count, 1);
+
+		let a = Arc::new(ConditionVariable::new(0));
+		let b1 = Arc::new(ConditionVariable::new(0));
+		let b2 = b1.clone();
+		spawn(move || {
+			sleep(Duration::from_millis(100));
+			b2.set(1, Notify::All);
+		});
+
+		assert_eq!(wait_for_either(&*a, |v| *v == 1, &*b1, |v| *v == 1), Ok(Either::Right(1)));
+		assert_eq!(a.stats().wait_count, 1);
+		assert_eq!(b1.stats().wait_count, 1);
+		assert_eq!(a.stats().timeout_count, 0);
+	}
+
+	#[test]
+	fn test_notify_many_capped_at_waiters() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.wait_for(1).unwrap());
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+
+		let before = Instant::now();
+		cvar1.set(1, Notify::Many(usize::MAX));
+		assert!(before.elapsed() < Duration::from_secs(1));
+		assert_eq!(cvar1.get().unwrap(), 1);
+		assert_eq!(waiter.join().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_set_one_safe_hops_expire() {
+		let cvar1 = Arc::new(ConditionVariable::new(()));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.wait_until_ms(5000));
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+		cvar1.set_one_safe(());
+		assert!(waiter.join().unwrap());
+
+		let (guard, notified) = cvar1.wait_ms(50).unwrap();
+		assert!(!notified);
+		drop(guard);
+		assert_eq!(cvar1.stats().notify_one_count, 1);
+	}
+
+	#[test]
+	fn test_wait_for_condition_unlocked_sees_lock_writes() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.wait_for_condition_unlocked(|v| *v == 1).unwrap());
+		while cvar1.waiter_count() == 0 {
+			sleep(Duration::from_millis(1));
+		}
+		sleep(Duration::from_millis(20));
+
+		*cvar1.lock().unwrap() = 1;
+		cvar1.notify_all();
+		assert_eq!(waiter.join().unwrap(), 1);
+		assert_eq!(cvar1.generation(), 1);
+	}
+
+	#[test]
+	fn test_fast_path_skips_waiter_bookkeeping() {
+		let cvar = ConditionVariable::new(1);
+		let unregistered = |_: &i32| cvar.waiter_count() == 0;
+
+		assert_eq!(cvar.wait_for_condition_mut(|v| unregistered(v)), Ok(()));
+		assert_eq!(cvar.wait_for_condition_bounded(unregistered, 0), Ok(true));
+		assert_eq!(cvar.set_and_wait_for(2, unregistered), Ok(2));
+		assert_eq!(cvar.wait_for_condition_detect_livelock(unregistered, 0), Ok(2));
+		assert_eq!(cvar.stats().wait_count, 4);
+	}
+}
+
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_tests {
+	extern crate std;
+
+	use alloc::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_spin_get_set_wait() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let waiter = spawn(move || cvar2.wait_for(2).unwrap());
+
+		cvar1.set(1, Notify::All);
+		assert_eq!(cvar1.get().unwrap(), 1);
+		cvar1.set(2, Notify::All);
+		assert_eq!(waiter.join().unwrap(), 2);
 	}
 }
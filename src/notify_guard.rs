@@ -0,0 +1,82 @@
+use core::ops::{Deref, DerefMut};
+
+use crate::{ConditionVariable, Notify};
+use crate::sync::MutexGuard;
+
+/// write access to the value that notifies when dropped, see `ConditionVariable::lock_and_notify()`
+///
+/// the notify also goes out on early returns and while unwinding from a panic, in which case
+/// waiters wake up to a poisoned lock.
+pub struct NotifyGuard<'a, T> {
+	cvar: &'a ConditionVariable<T>,
+	data: MutexGuard<'a, T>,
+	notify: Notify,
+}
+
+impl<'a, T> Deref for NotifyGuard<'a, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		&self.data
+	}
+}
+
+impl<'a, T> DerefMut for NotifyGuard<'a, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		&mut self.data
+	}
+}
+
+impl<'a, T> Drop for NotifyGuard<'a, T> {
+	fn drop(&mut self) {
+		let (_, cvar) = &self.cvar.pair;
+
+		// the lock is released only after this, when `data` is dropped
+		self.cvar.changed(cvar, &*self.data, self.notify);
+	}
+}
+
+impl<T> ConditionVariable<T> {
+	/// locks the value for writing; `notify` is sent when the returned guard goes out of scope.
+	/// `None` while frozen
+	pub fn lock_and_notify(&self, notify: Notify) -> Option<NotifyGuard<'_, T>> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock()).unwrap();
+		if self.is_frozen() {
+			return None;
+		}
+
+		Some(NotifyGuard {
+			cvar: self,
+			data,
+			notify,
+		})
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_lock_and_notify() {
+		let cvar1 = Arc::new(ConditionVariable::new(Vec::new()));
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			let mut data = cvar2.lock_and_notify(Notify::All).unwrap();
+			data.push(1);
+			if data.len() == 1 {
+				return;
+			}
+			data.push(2);
+		});
+
+		assert_eq!(cvar1.wait_for_condition(|v| !v.is_empty()).unwrap(), vec![1]);
+	}
+}
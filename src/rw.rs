@@ -0,0 +1,136 @@
+use std::sync::{Condvar, Mutex, PoisonError, RwLock, RwLockReadGuard};
+
+use crate::{Notify, WaitError};
+
+/// like `ConditionVariable`, but the value sits behind an `RwLock`, so readers don't block each other
+///
+/// `Condvar` only works with a `Mutex`, so waiters block on a separate mutex that writers take
+/// after releasing the write lock; a waiter holds it from checking the value until it sleeps,
+/// which is what keeps it from missing a write.
+pub struct RwConditionVariable<T> {
+	value: RwLock<T>,
+	signal: (Mutex<()>, Condvar),
+}
+
+impl<T> RwConditionVariable<T> {
+	pub fn new(value: T) -> RwConditionVariable<T> {
+		RwConditionVariable {
+			value: RwLock::new(value),
+			signal: (Mutex::new(()), Condvar::new()),
+		}
+	}
+
+	pub fn set(&self, value: T, notify: Notify) {
+		self.update(|data| *data = value, notify);
+	}
+
+	/// mutates the value under the write lock and notifies afterwards
+	pub fn update<F:FnOnce(&mut T)>(&self, f: F, notify: Notify) {
+		f(&mut *self.value.write().unwrap());
+
+		self.send_notify(notify);
+	}
+
+	/// runs `f` on the value under a read lock, concurrently with other readers
+	pub fn with_lock<R, F:FnOnce(&T) -> R>(&self, f: F) -> Result<R, PoisonError<RwLockReadGuard<'_, T>>> {
+		let data = self.value.read()?;
+
+		Ok(f(&*data))
+	}
+
+	fn send_notify(&self, notify: Notify) {
+		let (lock, cvar) = &self.signal;
+
+		let _signal = lock.lock().unwrap();
+
+		match notify {
+			Notify::None => {},
+			Notify::One => cvar.notify_one(),
+			Notify::All => cvar.notify_all(),
+			Notify::Many(n) => for _ in 0..n { cvar.notify_one() },
+		}
+	}
+
+	/// like `ConditionVariable::wait_for_condition_locked`, returning the still-held read guard
+	pub fn wait_for_condition_locked<F:FnMut(&T) -> bool>(&self, mut cond_func: F)
+		-> Result<RwLockReadGuard<'_, T>, WaitError>
+	{
+		let (lock, cvar) = &self.signal;
+
+		let mut signal = lock.lock()?;
+		loop {
+			let data = self.value.read()?;
+			if cond_func(&*data) {
+				return Ok(data);
+			}
+			drop(data);
+
+			signal = cvar.wait(signal)?;
+		}
+	}
+}
+
+impl<T:Clone> RwConditionVariable<T> {
+	pub fn get(&self) -> Result<T, PoisonError<RwLockReadGuard<'_, T>>> {
+		self.with_lock(T::clone)
+	}
+
+	pub fn wait_for_condition<F:FnMut(&T) -> bool>(&self, cond_func: F) -> Result<T, WaitError> {
+		let data = self.wait_for_condition_locked(cond_func)?;
+
+		Ok(data.clone())
+	}
+}
+
+impl<T:PartialEq+Clone> RwConditionVariable<T> {
+	pub fn wait_for(&self, expected: T) -> Result<T, WaitError> {
+		self.wait_for_condition(|actual| *actual == expected)
+	}
+}
+
+impl<T:Default> Default for RwConditionVariable<T> {
+	fn default() -> RwConditionVariable<T> {
+		RwConditionVariable::new(T::default())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::RwConditionVariable;
+
+	#[test]
+	fn test_rw_wait_for() {
+		let cvar1 = Arc::new(RwConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let readers: Vec<_> = (0..4).map(|_| {
+			let cvar = cvar1.clone();
+			spawn(move || cvar.wait_for(3).unwrap())
+		}).collect();
+
+		spawn(move || {
+			for i in 1..4 {
+				cvar2.set(i, Notify::All);
+			}
+		});
+
+		for reader in readers {
+			assert_eq!(reader.join().unwrap(), 3);
+		}
+		assert_eq!(cvar1.with_lock(|v| *v).unwrap(), 3);
+		assert_eq!(cvar1.get().unwrap(), 3);
+	}
+
+	#[test]
+	fn test_rw_wait_for_condition_fn_mut() {
+		let cvar = RwConditionVariable::new(1);
+
+		let mut checks = 0;
+		assert_eq!(cvar.wait_for_condition(|v| { checks += 1; *v == 1 }).unwrap(), 1);
+		assert_eq!(checks, 1);
+	}
+}
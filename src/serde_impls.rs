@@ -0,0 +1,39 @@
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Error, Serialize, Serializer};
+
+use crate::ConditionVariable;
+
+/// serializes a snapshot of the current value
+impl<T:Serialize> Serialize for ConditionVariable<T> {
+	fn serialize<S:Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let (lock, _) = &self.pair;
+
+		let data = self.recover(lock.lock()).map_err(|_| S::Error::custom("lock poisoned by a panicking thread"))?;
+
+		data.serialize(serializer)
+	}
+}
+
+impl<'de, T:Deserialize<'de>> Deserialize<'de> for ConditionVariable<T> {
+	fn deserialize<D:Deserializer<'de>>(deserializer: D) -> Result<ConditionVariable<T>, D::Error> {
+		T::deserialize(deserializer).map(ConditionVariable::new)
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_serde_round_trip() {
+		let cvar = ConditionVariable::new(1u32);
+		cvar.set(42, Notify::All);
+
+		let json = serde_json::to_string(&cvar).unwrap();
+		assert_eq!(json, "42");
+
+		let cvar: ConditionVariable<u32> = serde_json::from_str(&json).unwrap();
+		assert_eq!(cvar.get().unwrap(), 42);
+	}
+}
@@ -0,0 +1,53 @@
+use alloc::sync::Arc;
+use core::ops::Deref;
+
+use crate::ConditionVariable;
+
+/// cheaply cloneable handle to a shared `ConditionVariable`
+pub struct SharedConditionVariable<T> {
+	inner: Arc<ConditionVariable<T>>,
+}
+
+impl<T> SharedConditionVariable<T> {
+	pub fn new(value: T) -> SharedConditionVariable<T> {
+		SharedConditionVariable {
+			inner: Arc::new(ConditionVariable::new(value)),
+		}
+	}
+}
+
+impl<T> Clone for SharedConditionVariable<T> {
+	fn clone(&self) -> SharedConditionVariable<T> {
+		SharedConditionVariable {
+			inner: self.inner.clone(),
+		}
+	}
+}
+
+impl<T> Deref for SharedConditionVariable<T> {
+	type Target = ConditionVariable<T>;
+
+	fn deref(&self) -> &ConditionVariable<T> {
+		&self.inner
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::SharedConditionVariable;
+
+	#[test]
+	fn test_shared_wait_for() {
+		let cvar1 = SharedConditionVariable::new(false);
+		let cvar2 = cvar1.clone();
+
+		spawn(move || {
+			cvar2.set(true, Notify::All);
+		});
+
+		cvar1.wait_for(true).unwrap();
+	}
+}
@@ -0,0 +1,65 @@
+use crossbeam_channel::{bounded, Receiver, TrySendError};
+
+use crate::ConditionVariable;
+
+impl<T> ConditionVariable<T> {
+	/// a channel that receives `()` after writes, so changes can take part in a crossbeam `select!`.
+	/// no thread is involved: it `subscribe`s a callback that `try_send`s into a channel of capacity 1,
+	/// so a burst of writes reads as one signal, and every write pays for a `try_send`. once the
+	/// receiver is dropped, the next write removes the subscription
+	pub fn as_signal(&self) -> Receiver<()> {
+		let (sender, receiver) = bounded(1);
+
+		self.subscribe_while(move |_| !matches!(sender.try_send(()), Err(TrySendError::Disconnected(_))));
+		receiver
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::thread::{sleep, spawn};
+	use std::time::Duration;
+
+	use crossbeam_channel::{after, select};
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_as_signal() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+		let signal = cvar1.as_signal();
+
+		select! {
+			recv(signal) -> _ => panic!("signaled without a write"),
+			recv(after(Duration::from_millis(10))) -> _ => {},
+		}
+
+		spawn(move || {
+			sleep(Duration::from_millis(10));
+			cvar2.set(1, Notify::All);
+		});
+
+		select! {
+			recv(signal) -> res => assert!(res.is_ok()),
+			recv(after(Duration::from_secs(10))) -> _ => panic!("timed out"),
+		}
+		assert_eq!(cvar1.get().unwrap(), 1);
+	}
+
+	#[test]
+	fn test_as_signal_dropped_receivers_unsubscribe() {
+		let cvar = ConditionVariable::new(0);
+		let signal = cvar.as_signal();
+		for _ in 0..100 {
+			drop(cvar.as_signal());
+		}
+		assert_eq!(cvar.subscribers.lock().unwrap().len(), 101);
+
+		cvar.set(1, Notify::All);
+		assert_eq!(cvar.subscribers.lock().unwrap().len(), 1);
+		assert!(signal.try_recv().is_ok());
+	}
+}
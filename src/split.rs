@@ -0,0 +1,114 @@
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+use crate::sync::{MutexGuard, PoisonError};
+use crate::{ConditionVariable, Notify, WaitError};
+#[cfg(feature = "std")]
+use crate::TimedWait;
+
+/// the producer half of `ConditionVariable::split`: can write, but not wait
+pub struct Setter<T> {
+	inner: Arc<ConditionVariable<T>>,
+}
+
+/// the consumer half of `ConditionVariable::split`: can read and wait, but not write
+pub struct Waiter<T> {
+	inner: Arc<ConditionVariable<T>>,
+}
+
+impl<T> ConditionVariable<T> {
+	/// hands out producer and consumer handles to one shared condition variable, so each side
+	/// only sees the operations of its role
+	pub fn split(self) -> (Setter<T>, Waiter<T>) {
+		let inner = Arc::new(self);
+
+		(Setter { inner: inner.clone() }, Waiter { inner })
+	}
+}
+
+impl<T> Setter<T> {
+	pub fn set(&self, value: T, notify: Notify) -> bool {
+		self.inner.set(value, notify)
+	}
+
+	pub fn update<F:FnOnce(&mut T)>(&self, f: F, notify: Notify) -> bool {
+		self.inner.update(f, notify)
+	}
+
+	pub fn notify(&self, notify: Notify) {
+		self.inner.notify(notify);
+	}
+}
+
+impl<T:Clone> Waiter<T> {
+	pub fn get(&self) -> Result<T, PoisonError<MutexGuard<'_, T>>> {
+		self.inner.get()
+	}
+
+	pub fn wait_for_condition<F:FnMut(&T) -> bool>(&self, cond_func: F) -> Result<T, WaitError> {
+		self.inner.wait_for_condition(cond_func)
+	}
+}
+
+#[cfg(feature = "std")]
+impl<T> Waiter<T> {
+	pub fn timed_wait_for_condition<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<TimedWait, WaitError>
+	{
+		self.inner.timed_wait_for_condition(cond_func, timeout)
+	}
+}
+
+impl<T:PartialEq+Clone> Waiter<T> {
+	pub fn wait_for(&self, expected: T) -> Result<T, WaitError> {
+		self.inner.wait_for(expected)
+	}
+
+	pub fn wait_for_change(&self, last: T) -> Result<T, WaitError> {
+		self.inner.wait_for_change(last)
+	}
+}
+
+impl<T> Clone for Setter<T> {
+	fn clone(&self) -> Setter<T> {
+		Setter {
+			inner: self.inner.clone(),
+		}
+	}
+}
+
+impl<T> Clone for Waiter<T> {
+	fn clone(&self) -> Waiter<T> {
+		Waiter {
+			inner: self.inner.clone(),
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::thread::spawn;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[test]
+	fn test_split() {
+		let (setter, waiter) = ConditionVariable::new(0).split();
+
+		let waiters: Vec<_> = (0..2).map(|_| {
+			let waiter = waiter.clone();
+			spawn(move || waiter.wait_for(2).unwrap())
+		}).collect();
+
+		let setter2 = setter.clone();
+		spawn(move || setter2.set(1, Notify::All)).join().unwrap();
+		setter.update(|v| *v += 1, Notify::All);
+
+		for handle in waiters {
+			assert_eq!(handle.join().unwrap(), 2);
+		}
+		assert_eq!(waiter.get().unwrap(), 2);
+	}
+}
@@ -0,0 +1,111 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::ConditionVariable;
+
+/// async counterpart of `Changes`, see `ConditionVariable::stream()`
+///
+/// like `Changes` it yields the latest value, so rapid writes may be coalesced; a write counts as a
+/// change even if the value stayed the same, and so does every `lock()`, which is how writes through
+/// the raw guard (announced with `notify_one`/`notify_all`) show up. the stream ends if the lock is
+/// poisoned or the condition variable is closed.
+pub struct ChangeStream<'a, T> {
+	cvar: &'a ConditionVariable<T>,
+	seen: Option<u64>,
+}
+
+impl<'a, T> Unpin for ChangeStream<'a, T> {}
+
+impl<'a, T:Clone> Stream for ChangeStream<'a, T> {
+	type Item = T;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+		let this = self.get_mut();
+		let cvar = this.cvar;
+		let (lock, _) = &cvar.pair;
+
+		let actual = match cvar.recover(lock.lock()) {
+			Ok(actual) => actual,
+			Err(_) => return Poll::Ready(None),
+		};
+
+		let generation = cvar.generation();
+		if this.seen.is_none_or(|seen| crate::newer_generation(generation, seen)) {
+			this.seen = Some(generation);
+			return Poll::Ready(Some(actual.clone()));
+		}
+		if cvar.is_closed() {
+			return Poll::Ready(None);
+		}
+
+		cvar.register_waker(cx.waker());
+		drop(actual);
+
+		Poll::Pending
+	}
+}
+
+impl<T:Clone> ConditionVariable<T> {
+	/// yields the current value right away, then the value after each write that notifies
+	pub fn stream(&self) -> ChangeStream<'_, T> {
+		ChangeStream {
+			cvar: self,
+			seen: None,
+		}
+	}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+	use std::future::poll_fn;
+	use std::pin::Pin;
+	use std::sync::Arc;
+
+	use futures_core::Stream;
+
+	use crate::Notify;
+	use crate::ConditionVariable;
+
+	#[tokio::test]
+	async fn test_stream() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let consumer = tokio::spawn(async move {
+			let mut stream = cvar2.stream();
+			let mut seen = Vec::new();
+			while let Some(v) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+				seen.push(v);
+			}
+			seen
+		});
+
+		for i in 1..4 {
+			tokio::task::yield_now().await;
+			cvar1.set(i, Notify::All);
+		}
+		cvar1.close();
+
+		let seen = consumer.await.unwrap();
+		assert_eq!(seen[0], 0);
+		assert_eq!(*seen.last().unwrap(), 3);
+		assert!(seen.windows(2).all(|w| w[0] < w[1]));
+	}
+
+	#[tokio::test]
+	async fn test_stream_sees_lock_writes() {
+		let cvar1 = Arc::new(ConditionVariable::new(0));
+		let cvar2 = cvar1.clone();
+
+		let mut stream = cvar1.stream();
+		assert_eq!(poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await, Some(0));
+
+		tokio::spawn(async move {
+			*cvar2.lock().unwrap() = 5;
+			cvar2.notify_all();
+		});
+		assert_eq!(poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await, Some(5));
+	}
+}
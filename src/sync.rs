@@ -0,0 +1,235 @@
+//! lock primitives used internally: `std::sync` by default, `parking_lot` with the `parking_lot` feature
+//! and `spin` with the `spin` feature but without `std`. with `std` on, `spin` is ignored, so a
+//! dependency enabling it can't swap everyone's condvar for a busy-wait
+
+#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+pub use std::sync::{Mutex, Condvar, MutexGuard};
+
+#[cfg(feature = "parking_lot")]
+pub use self::parking::{Mutex, Condvar, MutexGuard};
+
+#[cfg(all(feature = "spin", not(feature = "std")))]
+pub use self::spinning::{Mutex, Condvar, MutexGuard};
+
+#[cfg(feature = "std")]
+pub use std::sync::{PoisonError, LockResult, TryLockError};
+
+#[cfg(not(feature = "std"))]
+pub use self::poison::{PoisonError, LockResult, TryLockError};
+
+#[cfg(all(not(feature = "std"), not(feature = "spin")))]
+compile_error!("without the `std` feature, the `spin` feature is required for a lock implementation");
+
+/// `Condvar::wait_timeout` without the timed-out flag: the wait loops check their own deadline
+#[cfg(all(feature = "std", not(feature = "parking_lot")))]
+pub fn wait_timeout<'a, T>(cvar: &Condvar, guard: MutexGuard<'a, T>, timeout: std::time::Duration)
+	-> LockResult<MutexGuard<'a, T>>
+{
+	cvar.wait_timeout(guard, timeout)
+		.map(|(guard, _)| guard)
+		.map_err(|poison| PoisonError::new(poison.into_inner().0))
+}
+
+#[cfg(feature = "parking_lot")]
+pub fn wait_timeout<'a, T>(cvar: &Condvar, guard: MutexGuard<'a, T>, timeout: std::time::Duration)
+	-> LockResult<MutexGuard<'a, T>>
+{
+	cvar.wait_timeout(guard, timeout)
+}
+
+/// `parking_lot` behind the `std::sync` API; its locks never poison, so every `Result` is `Ok`
+#[cfg(feature = "parking_lot")]
+mod parking {
+	use std::sync::{LockResult, TryLockError, TryLockResult};
+	use std::time::Duration;
+
+	pub use parking_lot::MutexGuard;
+
+	pub struct Mutex<T>(parking_lot::Mutex<T>);
+
+	impl<T> Mutex<T> {
+		pub const fn new(value: T) -> Mutex<T> {
+			Mutex(parking_lot::Mutex::new(value))
+		}
+
+		pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+			Ok(self.0.lock())
+		}
+
+		pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+			self.0.try_lock().ok_or(TryLockError::WouldBlock)
+		}
+
+		pub fn into_inner(self) -> LockResult<T> {
+			Ok(self.0.into_inner())
+		}
+
+		pub fn get_mut(&mut self) -> LockResult<&mut T> {
+			Ok(self.0.get_mut())
+		}
+
+		pub fn is_poisoned(&self) -> bool {
+			false
+		}
+
+		pub fn clear_poison(&self) {}
+	}
+
+	pub struct Condvar(parking_lot::Condvar);
+
+	impl Condvar {
+		pub const fn new() -> Condvar {
+			Condvar(parking_lot::Condvar::new())
+		}
+
+		pub fn notify_one(&self) {
+			self.0.notify_one();
+		}
+
+		pub fn notify_all(&self) {
+			self.0.notify_all();
+		}
+
+		pub fn wait<'a, T>(&self, mut guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+			self.0.wait(&mut guard);
+			Ok(guard)
+		}
+
+		pub fn wait_timeout<'a, T>(&self, mut guard: MutexGuard<'a, T>, timeout: Duration)
+			-> LockResult<MutexGuard<'a, T>>
+		{
+			self.0.wait_for(&mut guard, timeout);
+			Ok(guard)
+		}
+	}
+}
+
+/// a spinlock and a busy-waiting condvar; nothing here needs an OS, and spinlocks never poison
+#[cfg(all(feature = "spin", not(feature = "std")))]
+mod spinning {
+	use core::hint::spin_loop;
+	use core::ops::{Deref, DerefMut};
+	use core::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::{LockResult, TryLockError};
+
+	pub struct Mutex<T>(spin::Mutex<T>);
+
+	/// keeps track of its mutex, so the condvar can unlock and relock it
+	pub struct MutexGuard<'a, T> {
+		lock: &'a spin::Mutex<T>,
+		guard: spin::MutexGuard<'a, T>,
+	}
+
+	impl<'a, T> Deref for MutexGuard<'a, T> {
+		type Target = T;
+
+		fn deref(&self) -> &T {
+			&self.guard
+		}
+	}
+
+	impl<'a, T> DerefMut for MutexGuard<'a, T> {
+		fn deref_mut(&mut self) -> &mut T {
+			&mut self.guard
+		}
+	}
+
+	impl<T> Mutex<T> {
+		pub const fn new(value: T) -> Mutex<T> {
+			Mutex(spin::Mutex::new(value))
+		}
+
+		pub fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+			Ok(MutexGuard { lock: &self.0, guard: self.0.lock() })
+		}
+
+		pub fn try_lock(&self) -> Result<MutexGuard<'_, T>, TryLockError<MutexGuard<'_, T>>> {
+			match self.0.try_lock() {
+				Some(guard) => Ok(MutexGuard { lock: &self.0, guard }),
+				None => Err(TryLockError::WouldBlock),
+			}
+		}
+
+		pub fn into_inner(self) -> LockResult<T> {
+			Ok(self.0.into_inner())
+		}
+
+		pub fn get_mut(&mut self) -> LockResult<&mut T> {
+			Ok(self.0.get_mut())
+		}
+
+		pub fn is_poisoned(&self) -> bool {
+			false
+		}
+
+		pub fn clear_poison(&self) {}
+	}
+
+	/// waiters spin until the notification counter moves; every notify wakes all of them,
+	/// which is allowed since callers already have to cope with spurious wakeups
+	pub struct Condvar {
+		seq: AtomicUsize,
+	}
+
+	impl Condvar {
+		pub const fn new() -> Condvar {
+			Condvar { seq: AtomicUsize::new(0) }
+		}
+
+		pub fn notify_one(&self) {
+			self.notify_all();
+		}
+
+		pub fn notify_all(&self) {
+			self.seq.fetch_add(1, Ordering::SeqCst);
+		}
+
+		pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+			let seq = self.seq.load(Ordering::SeqCst);
+			let lock = guard.lock;
+			drop(guard);
+
+			while self.seq.load(Ordering::SeqCst) == seq {
+				spin_loop();
+			}
+
+			Ok(MutexGuard { lock, guard: lock.lock() })
+		}
+	}
+}
+
+/// stand-ins for the `std::sync` poison types, so the public API has the same shape without `std`
+#[cfg(not(feature = "std"))]
+mod poison {
+	use core::fmt;
+
+	pub struct PoisonError<G> {
+		guard: G,
+	}
+
+	impl<G> PoisonError<G> {
+		pub fn into_inner(self) -> G {
+			self.guard
+		}
+	}
+
+	impl<G> fmt::Debug for PoisonError<G> {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			f.debug_struct("PoisonError").finish()
+		}
+	}
+
+	impl<G> fmt::Display for PoisonError<G> {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "poisoned lock: another task failed inside")
+		}
+	}
+
+	pub enum TryLockError<G> {
+		Poisoned(PoisonError<G>),
+		WouldBlock,
+	}
+
+	pub type LockResult<G> = Result<G, PoisonError<G>>;
+}
@@ -0,0 +1,563 @@
+use std::hint::spin_loop;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::{Clock, ConditionVariable, Notify, SystemClock, TimedWait, WaitError, WaitOutcome, WaitResult, WaiterGuard};
+use crate::sync::{self, Condvar, MutexGuard, LockResult, PoisonError, TryLockError};
+
+/// how long `wait_for_either` blocks on one side before checking the other
+const EITHER_POLL: Duration = Duration::from_millis(1);
+
+/// how often `wait_for_condition_cancellable` and waits with `attach_cancel` re-check the cancel flag
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// shortest interval `wait_for_condition_with_heartbeat` ticks at, so a zero one doesn't busy-loop
+const HEARTBEAT_MIN: Duration = Duration::from_millis(1);
+
+/// first sleep of `wait_for_condition_polling`, doubled after every failed check
+const POLL_START: Duration = Duration::from_millis(1);
+
+/// negative timeouts are treated as zero, i.e. the `_ms` waits check the condition once and return
+fn from_ms(timeout_ms: i64) -> Duration {
+	Duration::from_millis(if timeout_ms > 0 { timeout_ms as u64 } else { 0 })
+}
+
+/// how a wait went, see `wait_for_condition_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitStats {
+	/// times `cond_func` was checked: 1 if it held right away, more for each wakeup that didn't satisfy it
+	pub iterations: u32,
+	/// time spent in the wait, including acquiring the lock
+	pub elapsed: Duration,
+}
+
+/// one timeout shared by a chain of `wait_for_condition_within` calls, each using up what it waited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+	remaining: Duration,
+}
+
+impl Budget {
+	pub fn new(timeout: Duration) -> Budget {
+		Budget { remaining: timeout }
+	}
+
+	pub fn remaining(&self) -> Duration {
+		self.remaining
+	}
+
+	pub fn is_exhausted(&self) -> bool {
+		self.remaining == Duration::new(0, 0)
+	}
+}
+
+impl<T> ConditionVariable<T> {
+	/// makes every blocking wait on this condition variable fail with `WaitError::Cancelled` once
+	/// `flag` is set, e.g. from a SIGINT handler, without passing it to each call. a `Condvar`
+	/// can't be interrupted, so waits then block for at most 10ms at a time to re-check it.
+	/// replaces a previously attached flag
+	pub fn attach_cancel(&self, flag: Arc<AtomicBool>) {
+		*self.recover(self.cancel.lock()).unwrap() = Some(flag);
+	}
+
+	/// the time according to the clock given to `with_clock`, or `SystemClock`
+	fn now(&self) -> Instant {
+		match &self.clock {
+			Some(clock) => clock.now(),
+			None => SystemClock.now(),
+		}
+	}
+
+	pub(crate) fn cancel_requested(&self) -> bool {
+		let cancel = self.recover(self.cancel.lock()).unwrap();
+
+		cancel.as_ref().is_some_and(|flag| flag.load(Ordering::SeqCst))
+	}
+
+	/// blocks on the condvar for at most `timeout` (`None`: until notified), or at most `CANCEL_POLL`
+	/// with a cancel flag attached; fails right away if that flag is set
+	pub(crate) fn block_timeout<'a>(&self, cvar: &Condvar, guard: MutexGuard<'a, T>, timeout: Option<Duration>)
+		-> Result<MutexGuard<'a, T>, WaitError>
+	{
+		let polling = match &*self.recover(self.cancel.lock()).unwrap() {
+			Some(flag) if flag.load(Ordering::SeqCst) => return Err(WaitError::Cancelled),
+			Some(_) => true,
+			None => false,
+		};
+
+		let timeout = match timeout {
+			Some(timeout) if polling => timeout.min(CANCEL_POLL),
+			None if polling => CANCEL_POLL,
+			Some(timeout) => timeout,
+			None => return Ok(self.recover(cvar.wait(guard))?),
+		};
+
+		Ok(self.recover(sync::wait_timeout(cvar, guard, timeout))?)
+	}
+
+	/// like `wait_for_condition_locked`, reporting how often the condition was checked and how long it
+	/// took, e.g. to spot predicates that thrash on wakeups that don't concern them
+	pub fn wait_for_condition_stats<F:FnMut(&T) -> bool>(&self, mut cond_func: F) -> Result<WaitStats, WaitError> {
+		let start = Instant::now();
+		let mut iterations = 0u32;
+
+		let _data = self.wait_for_condition_locked(|actual| {
+			iterations = iterations.saturating_add(1);
+			cond_func(actual)
+		})?;
+
+		Ok(WaitStats {
+			iterations,
+			elapsed: start.elapsed(),
+		})
+	}
+
+	/// never reports `TimedOut` before `timeout_ms` has elapsed: spurious wakeups and notifies
+	/// that leave `cond_func` false just wait out the remaining time.
+	/// a zero or negative `timeout_ms` checks `cond_func` exactly once without waiting
+	pub fn wait_for_condition_ms<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout_ms: i64)
+		-> Result<TimedWait, WaitError>
+	{
+		self.timed_wait(cond_func, from_ms(timeout_ms))
+	}
+
+	/// like `wait_for_condition_ms`, but returns the still-held guard if `cond_func` held in time
+	pub fn wait_for_condition_ms_locked<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout_ms: i64)
+		-> Result<Option<MutexGuard<'_, T>>, WaitError>
+	{
+		let (actual, res) = self.timed_wait_locked(cond_func, from_ms(timeout_ms))?;
+
+		Ok(match res.result {
+			WaitResult::Satisfied => Some(actual),
+			WaitResult::TimedOut => None,
+		})
+	}
+
+	/// `Satisfied` if `cond_func` stopped holding before the timeout
+	pub fn wait_while_ms<F:FnMut(&T) -> bool>(&self, mut cond_func: F, timeout_ms: i64)
+		-> Result<TimedWait, WaitError>
+	{
+		self.wait_for_condition_ms(|actual| !cond_func(actual), timeout_ms)
+	}
+
+	/// drop-in for `std::sync::Condvar::wait_timeout_while`: waits while `condition` holds and returns
+	/// the guard plus whether the timeout elapsed first. it goes through the same loop as the other
+	/// timed waits rather than std's, so it works with every lock backend and honors `close`
+	pub fn wait_timeout_while<F:FnMut(&T) -> bool>(&self, timeout: Duration, mut condition: F)
+		-> Result<(MutexGuard<'_, T>, bool), WaitError>
+	{
+		let (actual, res) = self.timed_wait_locked(|actual| !condition(actual), timeout)?;
+
+		Ok((actual, res.result == WaitResult::TimedOut))
+	}
+
+	/// like `wait_for_condition_ms`, for a `Duration`
+	pub fn timed_wait_for_condition<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<TimedWait, WaitError>
+	{
+		self.timed_wait(cond_func, timeout)
+	}
+
+	#[deprecated(since = "0.1.4", note = "use `timed_wait_for_condition`, which returns a `TimedWait`")]
+	pub fn wait_for_condition_timeout<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<bool, WaitError>
+	{
+		self.timed_wait_for_condition(cond_func, timeout).map(|res| res.result.is_satisfied())
+	}
+
+	/// waits until `cond_func` holds or `deadline` has passed
+	pub fn wait_for_condition_deadline<F:FnMut(&T) -> bool>(&self, cond_func: F, deadline: Instant)
+		-> Result<TimedWait, WaitError>
+	{
+		let res = self.timed_wait_until(cond_func, Some(deadline), true).map(|(_, res)| res)?;
+		if res.result == WaitResult::TimedOut {
+			self.timed_out();
+		}
+
+		Ok(res)
+	}
+
+	/// `Ok(true)` once `cond_func` holds, `Ok(false)` once `cancel` is set; a `Condvar` can't be
+	/// interrupted, so `cancel` is re-checked every 10ms and on every notify
+	pub fn wait_for_condition_cancellable<F:FnMut(&T) -> bool>(&self, mut cond_func: F, cancel: &AtomicBool)
+		-> Result<bool, WaitError>
+	{
+		self.count_wait();
+		loop {
+			let mut held = false;
+			let res = self.poll_wait(|actual| {
+				held = cond_func(actual);
+				held || cancel.load(Ordering::SeqCst)
+			}, CANCEL_POLL).map(|(_, res)| res)?;
+
+			if held {
+				return Ok(true);
+			}
+			if res.result.is_satisfied() {
+				return Ok(false);
+			}
+		}
+	}
+
+	/// like `set` (so it also returns `false` while frozen), but gives up and returns `false` if the
+	/// lock can't be had within `timeout`; `Mutex` has no timed lock, so this busy-waits on `try_lock`
+	/// and burns a core while contended. the timeout is wall-clock time, even with a `Clock` set
+	pub fn try_set_for(&self, value: T, notify: Notify, timeout: Duration) -> bool {
+		let (lock, cvar) = &self.pair;
+		let deadline = Instant::now().checked_add(timeout);
+
+		let mut data = loop {
+			match lock.try_lock() {
+				Ok(data) => break data,
+				Err(TryLockError::Poisoned(poison)) => break self.recover(Err(poison)).unwrap(),
+				Err(TryLockError::WouldBlock) => {},
+			}
+			if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+				return false;
+			}
+			spin_loop();
+		};
+
+		self.store(cvar, &mut data, value, notify)
+	}
+
+	/// waits for at most what is left of `budget` and deducts the time it took;
+	/// `TimedOut` without checking `cond_func` if the budget is already used up
+	pub fn wait_for_condition_within<F:FnMut(&T) -> bool>(&self, cond_func: F, budget: &mut Budget)
+		-> Result<TimedWait, WaitError>
+	{
+		if budget.is_exhausted() {
+			return Ok(TimedWait {
+				result: WaitResult::TimedOut,
+				remaining: Duration::new(0, 0),
+				elapsed: Duration::new(0, 0),
+			});
+		}
+
+		let res = self.timed_wait(cond_func, budget.remaining)?;
+		budget.remaining = res.remaining;
+
+		Ok(res)
+	}
+
+	/// waits like `wait_for_condition_locked`, calling `on_tick` with the total time waited whenever
+	/// another `interval` passes without `cond_func` holding, e.g. to log that it's still waiting.
+	/// intervals below 1ms are raised to 1ms
+	pub fn wait_for_condition_with_heartbeat<F:FnMut(&T) -> bool, H:FnMut(Duration)>(&self, mut cond_func: F,
+		interval: Duration, mut on_tick: H) -> Result<(), WaitError>
+	{
+		let interval = interval.max(HEARTBEAT_MIN);
+		let start = self.now();
+		self.count_wait();
+
+		while !self.poll_wait(&mut cond_func, interval)?.1.result.is_satisfied() {
+			on_tick(self.now().saturating_duration_since(start));
+		}
+		Ok(())
+	}
+
+	/// like `wait_for_condition_deadline`, for callers sharing one absolute deadline across several waits
+	pub fn wait_for_condition_until<F:FnMut(&T) -> bool>(&self, cond_func: F, deadline: Instant)
+		-> Result<TimedWait, WaitError>
+	{
+		self.wait_for_condition_deadline(cond_func, deadline)
+	}
+
+	fn timed_wait<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<TimedWait, WaitError>
+	{
+		self.timed_wait_locked(cond_func, timeout).map(|(_, res)| res)
+	}
+
+	fn timed_wait_locked<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<(MutexGuard<'_, T>, TimedWait), WaitError>
+	{
+		let (actual, res) = self.timed_wait_until(cond_func, self.now().checked_add(timeout), true)?;
+		if res.result == WaitResult::TimedOut {
+			self.timed_out();
+		}
+
+		Ok((actual, res))
+	}
+
+	/// one step of a longer wait that polls something else in between; the caller counts the wait
+	/// once, and timing out a step isn't counted at all
+	fn poll_wait<F:FnMut(&T) -> bool>(&self, cond_func: F, timeout: Duration)
+		-> Result<(MutexGuard<'_, T>, TimedWait), WaitError>
+	{
+		self.timed_wait_until(cond_func, self.now().checked_add(timeout), false)
+	}
+
+	/// the timed wait loop; a `None` deadline (a timeout too far out for `Instant`) never expires.
+	/// every wait is for `deadline - now`, never a timeout minus accumulated waits, so spurious
+	/// wakeups can't make it drift. `count` is false for the slices of a longer wait that counts itself
+	fn timed_wait_until<F:FnMut(&T) -> bool>(&self, mut cond_func: F, deadline: Option<Instant>, count: bool)
+		-> Result<(MutexGuard<'_, T>, TimedWait), WaitError>
+	{
+		if count {
+			self.count_wait();
+		}
+		let (lock, cvar) = &self.pair;
+		let start = self.now();
+		let mut actual = self.recover(lock.lock())?;
+
+		let remaining_at = |now: Instant| match deadline {
+			Some(deadline) => deadline.saturating_duration_since(now),
+			None => Duration::MAX,
+		};
+		let remaining = || remaining_at(self.now());
+		// one reading for both, so `elapsed + remaining` never exceeds the timeout
+		let finish = |result| {
+			let now = self.now();
+			TimedWait {
+				result,
+				remaining: remaining_at(now),
+				elapsed: now.saturating_duration_since(start),
+			}
+		};
+
+		// fast path, see `wait_for_condition_locked`
+		if cond_func(&*actual) {
+			return Ok((actual, finish(WaitResult::Satisfied)));
+		}
+
+		let _waiting = WaiterGuard::register(self);
+		#[cfg(feature = "tracing")]
+		tracing::trace!(waiters = self.waiter_count(), timeout = ?remaining(), "waiting");
+
+		let result = loop {
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			self.relay(cvar);
+			actual = match deadline {
+				Some(_) if remaining() == Duration::new(0, 0) => break WaitResult::TimedOut,
+				Some(_) => self.block_timeout(cvar, actual, Some(remaining()))?,
+				None => self.block_timeout(cvar, actual, None)?,
+			};
+			#[cfg(feature = "tracing")]
+			tracing::trace!(waiters = self.waiter_count(), remaining = ?remaining(), "woke");
+
+			if cond_func(&*actual) {
+				break WaitResult::Satisfied;
+			}
+		};
+		#[cfg(feature = "tracing")]
+		if result == WaitResult::TimedOut {
+			tracing::trace!(waiters = self.waiter_count(), "timed out");
+		}
+
+		Ok((actual, finish(result)))
+	}
+}
+
+impl<T:Clone> ConditionVariable<T> {
+	/// last resort for targets where notifies get lost: never sleeps on the condvar, but re-checks
+	/// `cond_func` after sleeps starting at 1ms and doubling up to `max_interval`, so it doesn't care
+	/// whether anyone notifies. expect up to `max_interval` of extra latency and regular wakeups;
+	/// prefer `wait_for_condition` wherever notification works
+	pub fn wait_for_condition_polling<F:FnMut(&T) -> bool>(&self, mut cond_func: F, max_interval: Duration)
+		-> Result<T, WaitError>
+	{
+		let (lock, _) = &self.pair;
+		let _waiting = WaiterGuard::new(self);
+		let mut interval = POLL_START.min(max_interval);
+
+		loop {
+			let actual = self.recover(lock.lock())?;
+			if cond_func(&*actual) {
+				return Ok(actual.clone());
+			}
+			drop(actual);
+
+			if self.is_closed() {
+				return Err(WaitError::Closed);
+			}
+			if self.cancel_requested() {
+				return Err(WaitError::Cancelled);
+			}
+			sleep(interval);
+			interval = interval.saturating_mul(2).min(max_interval);
+		}
+	}
+
+	/// `wait_for_condition` with an optional timeout and an optional cancel flag in one call: `Ready`
+	/// with the satisfying value, `TimedOut` or `Cancelled`. with a flag it waits at most 10ms at a
+	/// time to re-check it, like `wait_for_condition_cancellable`
+	pub fn wait_for_condition_full<F:FnMut(&T) -> bool>(&self, mut cond_func: F, timeout: Option<Duration>,
+		cancel: Option<&AtomicBool>) -> Result<WaitOutcome<T>, WaitError>
+	{
+		let deadline = timeout.and_then(|timeout| self.now().checked_add(timeout));
+		self.count_wait();
+
+		loop {
+			let poll = cancel.and_then(|_| self.now().checked_add(CANCEL_POLL));
+			let step = match (deadline, poll) {
+				(Some(deadline), Some(poll)) => Some(deadline.min(poll)),
+				(deadline, None) => deadline,
+				(None, poll) => poll,
+			};
+
+			let mut cancelled = false;
+			let (actual, res) = self.timed_wait_until(|actual| cond_func(actual) || {
+				cancelled = cancel.is_some_and(|cancel| cancel.load(Ordering::SeqCst));
+				cancelled
+			}, step, false)?;
+
+			if cancelled {
+				return Ok(WaitOutcome::Cancelled);
+			}
+			if res.result.is_satisfied() {
+				return Ok(WaitOutcome::Ready(actual.clone()));
+			}
+			if deadline.is_some_and(|deadline| self.now() >= deadline) {
+				self.timed_out();
+				return Ok(WaitOutcome::TimedOut);
+			}
+		}
+	}
+
+	/// debounce: returns the value once nothing was written for `quiet`, restarting the quiet
+	/// period on every write (even one that leaves the value the same)
+	pub fn wait_for_stable(&self, quiet: Duration) -> Result<T, WaitError> {
+		self.count_wait();
+		loop {
+			let seen = self.generation();
+			let (actual, res) = self.poll_wait(|_| self.generation() != seen, quiet)?;
+
+			if res.result == WaitResult::TimedOut {
+				return Ok(actual.clone());
+			}
+		}
+	}
+}
+
+impl<T:PartialEq> ConditionVariable<T> {
+	pub fn wait_for_ms(&self, expected: T, timeout_ms: i64) -> Result<TimedWait, WaitError> {
+		self.wait_for_in_ms(&[expected], timeout_ms)
+	}
+
+	pub fn wait_for_in_ms(&self, expected: &[T], timeout_ms: i64)
+		-> Result<TimedWait, WaitError>
+	{
+		self.wait_for_condition_ms(|actual| expected.contains(actual), timeout_ms)
+	}
+
+	/// `Satisfied` if the value differed from `unwanted` before the timeout
+	pub fn wait_for_ne_ms(&self, unwanted: T, timeout_ms: i64) -> Result<TimedWait, WaitError> {
+		self.wait_for_condition_ms(|actual| *actual != unwanted, timeout_ms)
+	}
+
+	pub fn timed_wait_for(&self, expected: T, timeout: Duration) -> Result<TimedWait, WaitError> {
+		self.timed_wait_for_in(&[expected], timeout)
+	}
+
+	pub fn timed_wait_for_in(&self, expected: &[T], timeout: Duration) -> Result<TimedWait, WaitError> {
+		self.timed_wait_for_condition(|actual| expected.contains(actual), timeout)
+	}
+
+	#[deprecated(since = "0.1.4", note = "use `timed_wait_for`, which returns a `TimedWait`")]
+	pub fn wait_for_timeout(&self, expected: T, timeout: Duration)
+		-> Result<bool, WaitError>
+	{
+		self.timed_wait_for(expected, timeout).map(|res| res.result.is_satisfied())
+	}
+
+	#[deprecated(since = "0.1.4", note = "use `timed_wait_for_in`, which returns a `TimedWait`")]
+	pub fn wait_for_in_timeout(&self, expected: &[T], timeout: Duration)
+		-> Result<bool, WaitError>
+	{
+		self.timed_wait_for_in(expected, timeout).map(|res| res.result.is_satisfied())
+	}
+}
+
+impl<T:PartialEq+Clone> ConditionVariable<T> {
+	/// returns the value that satisfied the wait, or `None` on timeout
+	pub fn wait_for_value_ms(&self, expected: T, timeout_ms: i64) -> Result<Option<T>, WaitError> {
+		let (actual, res) = self.timed_wait_locked(|actual| *actual == expected, from_ms(timeout_ms))?;
+
+		Ok(match res.result {
+			WaitResult::Satisfied => Some(actual.clone()),
+			WaitResult::TimedOut => None,
+		})
+	}
+
+	/// waits for `expected` but gives up after `timeout`, reporting which one happened
+	pub fn wait_for_or_timeout(&self, expected: T, timeout: Duration) -> Result<WaitOutcome<T>, WaitError> {
+		let (actual, res) = self.timed_wait_locked(|actual| *actual == expected, timeout)?;
+
+		Ok(match res.result {
+			WaitResult::Satisfied => WaitOutcome::Ready(actual.clone()),
+			WaitResult::TimedOut => WaitOutcome::TimedOut,
+		})
+	}
+}
+
+impl ConditionVariable<()> {
+	/// waits for a notify (useful if T==()); `true` only if one actually arrived within `timeout_ms`,
+	/// spurious wakeups and `Notify::None` don't count. if the wait fails (poisoned, closed or
+	/// cancelled) it relocks and reports `false`
+	pub fn wait_ms(&self, timeout_ms: u32) -> LockResult<(MutexGuard<'_, ()>,bool)>
+	{
+		match self.wait_notified(Duration::from_millis(timeout_ms as u64)) {
+			Ok((guard, res)) => Ok((guard, res.result.is_satisfied())),
+			Err(_) => {
+				let (lock, _) = &self.pair;
+
+				self.recover(lock.lock())
+					.map(|guard| (guard, false))
+					.map_err(|poison| PoisonError::new((poison.into_inner(), false)))
+			},
+		}
+	}
+
+	/// like `wait_ms`, without the guard; `false` on timeout or if the lock is poisoned
+	pub fn wait_until_ms(&self, timeout_ms: u32) -> bool {
+		self.wait_timeout(Duration::from_millis(timeout_ms as u64)).is_satisfied()
+	}
+
+	/// `Satisfied` only if a notify actually arrived within `timeout`, spurious wakeups don't count;
+	/// `TimedOut` otherwise, also if the lock is poisoned or the condition variable was closed
+	pub fn wait_timeout(&self, timeout: Duration) -> WaitResult {
+		self.wait_notified(timeout).map_or(WaitResult::TimedOut, |(_, res)| res.result)
+	}
+
+	/// the notify-only wait: a wakeup counts as a notify only if the `notifications` sequence moved
+	fn wait_notified(&self, timeout: Duration) -> Result<(MutexGuard<'_, ()>, TimedWait), WaitError> {
+		let start = self.notifications.load(Ordering::SeqCst);
+		let notified = |_: &()| self.notifications.load(Ordering::SeqCst) != start;
+
+		self.timed_wait_locked(notified, timeout)
+	}
+}
+
+/// which side satisfied `wait_for_either`, with a clone of its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> {
+	Left(A),
+	Right(B),
+}
+
+/// blocks until `pred_a` holds for `a` or `pred_b` holds for `b`, checking `a` first;
+/// implemented by alternating short timed waits, so the side not currently waited on is seen with ~1ms delay
+pub fn wait_for_either<A:Clone, B:Clone, FA:FnMut(&A) -> bool, FB:FnMut(&B) -> bool>(
+	a: &ConditionVariable<A>, mut pred_a: FA, b: &ConditionVariable<B>, mut pred_b: FB)
+	-> Result<Either<A, B>, WaitError>
+{
+	a.count_wait();
+	b.count_wait();
+	loop {
+		let (actual, res) = a.poll_wait(&mut pred_a, EITHER_POLL)?;
+		if res.result.is_satisfied() {
+			return Ok(Either::Left(actual.clone()));
+		}
+		drop(actual);
+
+		let (actual, res) = b.poll_wait(&mut pred_b, EITHER_POLL)?;
+		if res.result.is_satisfied() {
+			return Ok(Either::Right(actual.clone()));
+		}
+	}
+}